@@ -543,6 +543,18 @@ errorToString err =
         NetworkError ->
             "Network error"
 
+        BadStatus 400 ->
+            "Invalid input, please enter a steam profile url, steam id or faceit nickname"
+
+        BadStatus 404 ->
+            "Player not found"
+
+        BadStatus 502 ->
+            "Steam or Faceit is currently not reachable, please try again later"
+
+        BadStatus 503 ->
+            "Service unavailable, please try again later"
+
         BadStatus resp ->
             String.fromInt resp
 
@@ -0,0 +1,165 @@
+use crate::error::LookupError;
+use crate::{redis, steam, tracking};
+
+/// Redis sorted set of the creation dates seen on public profiles, scored by account ID
+const OBSERVED_ANCHORS_KEY: &str = "account_anchors";
+/// Only one observed anchor is kept per this many account IDs, a few days of sign-ups,
+/// which bounds the set to a few thousand members
+const OBSERVED_ANCHOR_SPACING: u32 = 1_000_000;
+
+/// Account IDs are handed out in ascending order, so these (account ID, creation date) pairs
+/// are enough to place any account in time. Approximate, the anchors observed on public
+/// profiles refine them over time.
+static ANCHORS: &[(u32, i64)] = &[
+    (1, 1063324800),          // 2003-09-12, launch of Steam
+    (39734272, 1291161600),   // 2010-12-01, 76561198000000000
+    (139734272, 1372636800),  // 2013-07-01, 76561198100000000
+    (239734272, 1433116800),  // 2015-06-01, 76561198200000000
+    (339734272, 1456790400),  // 2016-03-01, 76561198300000000
+    (439734272, 1506816000),  // 2017-10-01, 76561198400000000
+    (1039734272, 1567296000), // 2019-09-01, 76561199000000000
+    (1139734272, 1604188800), // 2020-11-01, 76561199100000000
+    (1239734272, 1630454400), // 2021-09-01, 76561199200000000
+    (1439734272, 1661990400), // 2022-09-01, 76561199400000000
+    (1639734272, 1698796800), // 2023-11-01, 76561199600000000
+    (1839734272, 1727740800), // 2024-10-01, 76561199800000000
+];
+
+/// When a Steam account was created, as a unix timestamp
+#[derive(Clone, Copy)]
+pub struct AccountCreated {
+    pub timestamp: i64,
+    /// True if the profile hides its creation date and it was estimated from the account ID
+    pub estimated: bool,
+}
+
+/// The creation date of the account, estimated from its account ID if the profile hides it.
+/// A creation date the profile shows is kept as an anchor for later estimates.
+pub async fn account_created(steam_id: &str, time_created: Option<i64>) -> Option<AccountCreated> {
+    let account_id = steam::account_id(steam_id)?;
+
+    match time_created {
+        Some(timestamp) => {
+            if let Err(e) = observe(account_id, timestamp) {
+                tracking::track_error(&LookupError::Cache(e).to_string()).await;
+            }
+            Some(AccountCreated {
+                timestamp,
+                estimated: false,
+            })
+        }
+        None => {
+            let observed = observed_neighbours(account_id);
+            if let Err(e) = &observed {
+                tracking::track_error(&LookupError::Cache(e.clone()).to_string()).await;
+            }
+
+            estimate(account_id, &observed.unwrap_or_default()).map(|timestamp| AccountCreated {
+                timestamp,
+                estimated: true,
+            })
+        }
+    }
+}
+
+fn observe(account_id: u32, timestamp: i64) -> Result<(), String> {
+    if timestamp <= 0 {
+        return Ok(());
+    }
+
+    let bucket_start = account_id - account_id % OBSERVED_ANCHOR_SPACING;
+    let first_in_bucket = redis::zfirst_from(OBSERVED_ANCHORS_KEY, bucket_start as u64)?;
+    if first_in_bucket
+        .and_then(|member| parse_anchor(&member))
+        .is_some_and(|(anchor_id, _)| anchor_id - bucket_start < OBSERVED_ANCHOR_SPACING)
+    {
+        return Ok(());
+    }
+
+    redis::zadd(
+        OBSERVED_ANCHORS_KEY,
+        &format!("{}:{}", account_id, timestamp),
+        account_id as u64,
+    )
+}
+
+/// The observed anchors closest to the account ID on either side
+fn observed_neighbours(account_id: u32) -> Result<Vec<(u32, i64)>, String> {
+    let below = redis::zlast_until(OBSERVED_ANCHORS_KEY, account_id as u64)?;
+    let above = redis::zfirst_from(OBSERVED_ANCHORS_KEY, account_id as u64)?;
+
+    Ok(below
+        .into_iter()
+        .chain(above)
+        .filter_map(|member| parse_anchor(&member))
+        .collect())
+}
+
+/// Parses an observed anchor stored as `<account ID>:<timestamp>`
+fn parse_anchor(member: &str) -> Option<(u32, i64)> {
+    let (account_id, timestamp) = member.split_once(':')?;
+    Some((account_id.parse().ok()?, timestamp.parse().ok()?))
+}
+
+/// Interpolates the creation date between the closest anchors below and above the account ID.
+/// Accounts newer than every anchor get the date of the newest one, they can't be older than that.
+fn estimate(account_id: u32, observed: &[(u32, i64)]) -> Option<i64> {
+    let anchors = ANCHORS.iter().chain(observed);
+    let below = anchors
+        .clone()
+        .filter(|(anchor_id, _)| *anchor_id <= account_id)
+        .max_by_key(|(anchor_id, _)| *anchor_id);
+    let above = anchors
+        .filter(|(anchor_id, _)| *anchor_id >= account_id)
+        .min_by_key(|(anchor_id, _)| *anchor_id);
+
+    match (below, above) {
+        (Some(&(low_id, low_time)), Some(&(high_id, high_time))) if high_id > low_id => {
+            let progress = (account_id - low_id) as f64 / (high_id - low_id) as f64;
+            Some(low_time + ((high_time - low_time) as f64 * progress) as i64)
+        }
+        (Some(&(_, time)), _) | (None, Some(&(_, time))) => Some(time),
+        (None, None) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimate_interpolates_between_anchors() {
+        assert_eq!(estimate(89734272, &[]), Some(1331899200));
+    }
+
+    #[test]
+    fn estimate_of_an_anchor_is_its_date() {
+        assert_eq!(estimate(139734272, &[]), Some(1372636800));
+        assert_eq!(estimate(1, &[]), Some(1063324800));
+    }
+
+    #[test]
+    fn estimate_of_accounts_newer_than_every_anchor_is_the_newest_date() {
+        assert_eq!(estimate(2_000_000_000, &[]), Some(1727740800));
+        assert_eq!(estimate(u32::MAX, &[]), Some(1727740800));
+    }
+
+    #[test]
+    fn estimate_prefers_closer_observed_anchors() {
+        let observed = [(89734272, 1300000000)];
+
+        assert_eq!(estimate(89734272, &observed), Some(1300000000));
+        assert_eq!(estimate(64734272, &observed), Some(1295580800));
+        assert_eq!(estimate(114734272, &observed), Some(1336318400));
+    }
+
+    #[test]
+    fn parse_anchor_of_stored_members() {
+        assert_eq!(
+            parse_anchor("89734272:1300000000"),
+            Some((89734272, 1300000000))
+        );
+        assert_eq!(parse_anchor("89734272"), None);
+        assert_eq!(parse_anchor("a:b"), None);
+    }
+}
@@ -15,3 +15,8 @@ pub fn ensure_set() {
 pub fn get(key: &str) -> String {
     dotenv::var(key).unwrap_or_else(|_| panic!("Environment variable {} is not set", key))
 }
+
+/// Like `get`, but falls back to `default` for optional variables
+pub fn get_or(key: &str, default: &str) -> String {
+    dotenv::var(key).unwrap_or_else(|_| default.to_string())
+}
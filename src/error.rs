@@ -0,0 +1,96 @@
+use rocket::http::Status;
+use rocket::response::{self, Responder};
+use rocket::serde::json::Json;
+use rocket::Request;
+use serde::Serialize;
+use std::fmt;
+
+use crate::input::InputError;
+
+/// Everything that can go wrong while looking up a player
+#[derive(Debug)]
+pub enum LookupError {
+    InvalidInput(InputError),
+    VanityNotResolvable(String),
+    /// A short link or Faceit nickname that doesn't lead to a Steam profile
+    PlayerNotFound(String),
+    SteamApi(String),
+    FaceitApi(String),
+    Cache(String),
+    Serialization(String),
+}
+
+/// The JSON body of every error response
+#[derive(Serialize)]
+struct ErrorBody {
+    /// Stable, machine-readable error code
+    code: &'static str,
+    /// Why the input was rejected, only set for `invalid_input`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<&'static str>,
+    message: String,
+}
+
+impl LookupError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            LookupError::InvalidInput(_) => "invalid_input",
+            LookupError::VanityNotResolvable(_) => "vanity_not_resolvable",
+            LookupError::PlayerNotFound(_) => "player_not_found",
+            LookupError::SteamApi(_) => "steam_api_error",
+            LookupError::FaceitApi(_) => "faceit_api_error",
+            LookupError::Cache(_) => "cache_unavailable",
+            LookupError::Serialization(_) => "internal_error",
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        match self {
+            LookupError::InvalidInput(_) => Status::BadRequest,
+            LookupError::VanityNotResolvable(_) | LookupError::PlayerNotFound(_) => {
+                Status::NotFound
+            }
+            LookupError::SteamApi(_) | LookupError::FaceitApi(_) => Status::BadGateway,
+            LookupError::Cache(_) => Status::ServiceUnavailable,
+            LookupError::Serialization(_) => Status::InternalServerError,
+        }
+    }
+}
+
+impl fmt::Display for LookupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LookupError::InvalidInput(err) => write!(f, "{}", err),
+            LookupError::VanityNotResolvable(name) => {
+                write!(f, "Could not resolve steam id from vanity name: {}", name)
+            }
+            LookupError::PlayerNotFound(input) => write!(f, "Could not find player: {}", input),
+            LookupError::SteamApi(msg) => write!(f, "Steam API error: {}", msg),
+            LookupError::FaceitApi(msg) => write!(f, "Faceit API error: {}", msg),
+            LookupError::Cache(msg) => write!(f, "Cache error: {}", msg),
+            LookupError::Serialization(msg) => write!(f, "Error serializing player: {}", msg),
+        }
+    }
+}
+
+impl From<InputError> for LookupError {
+    fn from(err: InputError) -> Self {
+        LookupError::InvalidInput(err)
+    }
+}
+
+impl<'r> Responder<'r, 'static> for LookupError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let reason = match &self {
+            LookupError::InvalidInput(err) => Some(err.code()),
+            _ => None,
+        };
+        let body = ErrorBody {
+            code: self.code(),
+            reason,
+            message: self.to_string(),
+        };
+
+        (self.status(), Json(body)).respond_to(request)
+    }
+}
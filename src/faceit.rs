@@ -1,32 +1,133 @@
-use chrono::DateTime;
-use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
 use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
 
 use crate::env;
+use crate::error::LookupError;
+use crate::input::MatchWindow;
 
 #[derive(Deserialize, Serialize)]
 pub struct FaceitData {
     pub account_created: i64,
-    pub adr: f32,
     pub avatar: Option<String>,
     pub bans: Vec<FaceitBan>,
+    /// Lifetime stats of CS2 and CS:GO combined, None if Faceit has neither
+    pub career: Option<FaceitCareer>,
     pub country: String,
-    pub deaths: u16,
-    pub double_kills: u16,
+    /// Legacy CS:GO stats, None if the player never played CS:GO on Faceit
+    pub csgo: Option<FaceitCsgoData>,
     pub elo: u16,
+    pub elo_history: Option<EloHistory>,
+    pub level: u8,
+    /// The game `level` and `elo` are of, `csgo` for players who never played CS2 on Faceit
+    pub level_game: Option<String>,
+    /// Stats over every CS2 match ever played, to compare the window with
+    pub lifetime: Option<FaceitLifetimeStats>,
+    /// Performance per map over the match window, most played first
+    pub maps: Vec<FaceitMapStats>,
+    pub nickname: String,
+    #[serde(flatten)]
+    pub stats: MatchAggregates,
+}
+
+/// Stats aggregated over the matches of the window
+#[derive(Deserialize, Serialize)]
+pub struct MatchAggregates {
+    pub adr: f32,
+    pub deaths: u32,
+    pub double_kills: u16,
     pub headshot_percentage: f32,
-    pub headshots: u16,
+    pub headshots: u32,
     pub kd_ratio: f32,
-    pub kills: u16,
+    pub kills: u32,
     pub kr_ratio: f32,
-    pub level: u8,
-    pub losses: u8,
-    pub nickname: String,
+    pub losses: u16,
+    /// How many matches were aggregated, fewer than the window allows if the history is shorter
+    pub match_count: u32,
     pub penta_kills: u16,
     pub quadro_kills: u16,
     pub triple_kills: u16,
     pub win_rate: u8,
-    pub wins: u8,
+    pub wins: u16,
+}
+
+/// Stats over every match of a game ever played on Faceit, as Faceit counts them
+#[derive(Deserialize, Serialize)]
+pub struct FaceitLifetimeStats {
+    pub matches: u32,
+    pub wins: u32,
+    pub win_rate: u8,
+    pub kd_ratio: f32,
+    pub headshot_percentage: f32,
+    pub longest_win_streak: u32,
+    pub current_win_streak: u32,
+    /// Results of the last few matches as listed by Faceit, true for a win
+    pub recent_results: Vec<bool>,
+}
+
+/// Faceit stats of CS:GO, which Faceit replaced with CS2 in 2023
+#[derive(Deserialize, Serialize)]
+pub struct FaceitCsgoData {
+    pub level: u8,
+    pub elo: u16,
+    pub lifetime: Option<FaceitLifetimeStats>,
+    #[serde(flatten)]
+    pub stats: MatchAggregates,
+}
+
+/// Lifetime stats over both CS2 and CS:GO, the averages weighted by the match count of each game
+#[derive(Deserialize, Serialize)]
+pub struct FaceitCareer {
+    pub matches: u32,
+    pub wins: u32,
+    pub win_rate: u8,
+    pub kd_ratio: f32,
+    pub headshot_percentage: f32,
+    pub longest_win_streak: u32,
+}
+
+/// What was fetched for one game of a player
+pub struct FaceitGameResponses {
+    pub matches: Option<PlayerLastMatchesResponse>,
+    pub lifetime: Option<FaceitLifetimeStats>,
+}
+
+/// Performance of a player on one map over the match window
+#[derive(Deserialize, Serialize)]
+pub struct FaceitMapStats {
+    pub map: String,
+    pub matches: u32,
+    pub win_rate: u8,
+    pub kd_ratio: f32,
+    pub adr: f32,
+    pub headshot_percentage: f32,
+}
+
+/// How the ELO moved, over the matches of the window and the last few months
+#[derive(Deserialize, Serialize)]
+pub struct EloHistory {
+    /// ELO after each match of the window with ELO data, oldest first
+    pub timeline: Vec<EloPoint>,
+    /// Highest ELO of the timeline, so within the window
+    pub peak: Option<u16>,
+    /// Lowest ELO of the timeline, so within the window
+    pub lowest: Option<u16>,
+    /// Net change over the last 7, 30 and 90 days, None if there were no matches before that.
+    /// Measured on every match with ELO data, whether it is in the window or not.
+    pub change_7d: Option<i32>,
+    pub change_30d: Option<i32>,
+    pub change_90d: Option<i32>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct EloPoint {
+    pub match_id: String,
+    pub map: String,
+    /// Unix timestamp the match finished at
+    pub finished_at: i64,
+    pub elo: u16,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -81,7 +182,9 @@ pub struct Settings {
     pub language: String,
 }
 
-#[derive(Serialize, Deserialize)]
+// CS:GO matches lack some of the stats of CS2 matches, those are left empty
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
 pub struct MatchStats {
     #[serde(rename = "ADR")]
     pub adr: String,
@@ -167,27 +270,71 @@ pub struct PlayerLastMatchesResponse {
     pub items: Vec<MatchItem>,
 }
 
+const FACEIT_API_URL: &str = "https://open.faceit.com/data/v4";
+
+/// Starts a GET request to the Faceit data API, authorized with the FACEIT_API_KEY bearer token
+/// The API host can be overridden with FACEIT_API_URL.
+fn api_request(path: &str) -> reqwest::RequestBuilder {
+    reqwest::Client::new()
+        .get(format!(
+            "{}{}",
+            env::get_or("FACEIT_API_URL", FACEIT_API_URL),
+            path
+        ))
+        .bearer_auth(env::get("FACEIT_API_KEY"))
+}
+
+/// Fetch the Faceit account of a Steam ID, looked up by its CS2 account and then by its
+/// CS:GO one, veterans who never played CS2 on Faceit are only found by the latter
 pub async fn get_player_details(steam_id: &str) -> Option<FaceitPlayerDetailsAPIResponse> {
-    let api_url = format!(
-        "https://open.faceit.com/data/v4/players?game=cs2&game_player_id={}",
-        steam_id
-    );
+    for game in ["cs2", "csgo"] {
+        let response = api_request("/players")
+            .query(&[("game", game), ("game_player_id", steam_id)])
+            .send()
+            .await
+            .ok()?;
 
-    let mut headers = HeaderMap::new();
-    headers.insert(
-        AUTHORIZATION,
-        HeaderValue::from_str(&format!("Bearer {}", env::get("FACEIT_API_KEY"))).ok()?,
-    );
+        if response.status() == StatusCode::NOT_FOUND {
+            continue;
+        }
+        if !response.status().is_success() {
+            return None;
+        }
+        return response.json::<FaceitPlayerDetailsAPIResponse>().await.ok();
+    }
+
+    None
+}
+
+pub async fn get_player_details_by_nickname(
+    nickname: &str,
+) -> Result<FaceitPlayerDetailsAPIResponse, LookupError> {
+    match api_request("/players")
+        .query(&[("nickname", nickname)])
+        .send()
+        .await
+    {
+        Ok(response) if response.status() == StatusCode::NOT_FOUND => {
+            Err(LookupError::PlayerNotFound(nickname.to_string()))
+        }
+        Ok(response) => match response.error_for_status() {
+            Ok(response) => response
+                .json::<FaceitPlayerDetailsAPIResponse>()
+                .await
+                .map_err(|e| LookupError::FaceitApi(e.to_string())),
+            Err(e) => Err(LookupError::FaceitApi(e.to_string())),
+        },
+        Err(e) => Err(LookupError::FaceitApi(e.to_string())),
+    }
+}
 
-    let client = reqwest::Client::new();
+pub async fn get_player_details_by_id(player_id: &str) -> Option<FaceitPlayerDetailsAPIResponse> {
+    let path = format!("/players/{}", player_id);
 
-    match client.get(&api_url).headers(headers).send().await {
+    match api_request(&path).send().await {
         Ok(response) => {
             if response.status().is_success() {
-                match response.json::<FaceitPlayerDetailsAPIResponse>().await {
-                    Ok(data) => Some(data),
-                    Err(_) => None,
-                }
+                response.json::<FaceitPlayerDetailsAPIResponse>().await.ok()
             } else {
                 None
             }
@@ -196,45 +343,283 @@ pub async fn get_player_details(steam_id: &str) -> Option<FaceitPlayerDetailsAPI
     }
 }
 
-pub async fn get_player_last_matches(player_id: &str) -> Option<PlayerLastMatchesResponse> {
-    let api_url = format!(
-        "https://open.faceit.com/data/v4/players/{}/games/cs2/stats",
+#[derive(Deserialize)]
+pub struct FaceitSearchResponse {
+    pub items: Vec<FaceitSearchItem>,
+}
+
+#[derive(Deserialize)]
+pub struct FaceitSearchItem {
+    pub player_id: String,
+    pub nickname: String,
+    pub avatar: Option<String>,
+    pub country: Option<String>,
+    pub games: Vec<FaceitSearchGame>,
+}
+
+impl FaceitSearchItem {
+    pub fn cs2_skill_level(&self) -> Option<u8> {
+        self.games
+            .iter()
+            .find(|game| game.name == "cs2")
+            .map(|game| game.skill_level)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct FaceitSearchGame {
+    pub name: String,
+    // The search endpoint returns the skill level as string
+    #[serde(deserialize_with = "parse_number_or_string")]
+    pub skill_level: u8,
+}
+
+/// Searches CS2 players by (partial) nickname
+pub async fn search_players(nickname: &str) -> Result<Vec<FaceitSearchItem>, LookupError> {
+    match api_request("/search/players")
+        .query(&[("nickname", nickname), ("game", "cs2"), ("limit", "20")])
+        .send()
+        .await
+    {
+        Ok(response) => match response.error_for_status() {
+            Ok(response) => response
+                .json::<FaceitSearchResponse>()
+                .await
+                .map(|data| data.items)
+                .map_err(|e| LookupError::FaceitApi(e.to_string())),
+            Err(e) => Err(LookupError::FaceitApi(e.to_string())),
+        },
+        Err(e) => Err(LookupError::FaceitApi(e.to_string())),
+    }
+}
+
+/// Faceit returns at most this many matches per request
+const MATCH_PAGE_SIZE: u32 = 100;
+
+/// Fetch the matches of the window, most recent first, paging through the match history
+/// Returns None if any page fails, a partial history would skew the aggregates
+pub async fn get_player_last_matches(
+    player_id: &str,
+    game: &str,
+    window: &MatchWindow,
+) -> Option<PlayerLastMatchesResponse> {
+    let path = format!("/players/{}/games/{}/stats", player_id, game);
+    let is_in_window = |item: &MatchItem| match window.since {
+        // Match Finished At is in milliseconds
+        Some(since) => item.stats.match_finished_at as i64 >= since * 1000,
+        None => true,
+    };
+    let mut items: Vec<MatchItem> = Vec::new();
+
+    while (items.len() as u32) < window.matches {
+        let offset = items.len() as u32;
+        let limit = MATCH_PAGE_SIZE.min(window.matches - offset);
+
+        let response = api_request(&path)
+            .query(&[("offset", offset), ("limit", limit)])
+            .send()
+            .await
+            .ok()?;
+        if !response.status().is_success() {
+            return None;
+        }
+        let page = response.json::<PlayerLastMatchesResponse>().await.ok()?;
+
+        let is_last_page = (page.items.len() as u32) < limit
+            || page.items.last().is_some_and(|item| !is_in_window(item));
+        items.extend(page.items.into_iter().filter(is_in_window));
+
+        if is_last_page {
+            break;
+        }
+    }
+
+    Some(PlayerLastMatchesResponse {
+        start: 0,
+        end: items.len() as u64,
+        items,
+    })
+}
+
+/// The per-match ELO isn't part of the data API, it comes from the stats API faceit.com itself uses
+const FACEIT_STATS_URL: &str = "https://api.faceit.com/stats/v1";
+/// Entries per page of the per-match ELO
+const ELO_PAGE_SIZE: usize = 100;
+/// Pages of per-match ELO fetched at most, enough for the largest match window
+const MAX_ELO_PAGES: usize = 10;
+/// Days the net ELO change is reported for
+const ELO_CHANGE_DAYS: [i64; 3] = [7, 30, 90];
+
+/// ELO after a match, as returned by the stats API
+struct MatchElo {
+    elo: u16,
+    /// Unix timestamp in seconds
+    date: i64,
+}
+
+/// Fetch the ELO after each match, most recent first, far enough back for the matches
+/// and the longest change period
+/// Returns None on error
+async fn get_match_elo(player_id: &str, oldest_match: i64) -> Option<Vec<(String, MatchElo)>> {
+    let url = format!(
+        "{}/stats/time/users/{}/games/cs2",
+        env::get_or("FACEIT_STATS_URL", FACEIT_STATS_URL),
         player_id
     );
+    let longest_change = Utc::now().timestamp() - ELO_CHANGE_DAYS[2] * 24 * 60 * 60;
+    let oldest_needed = oldest_match.min(longest_change);
+    let mut entries = Vec::new();
 
-    let mut headers = HeaderMap::new();
-    headers.insert(
-        AUTHORIZATION,
-        HeaderValue::from_str(&format!("Bearer {}", env::get("FACEIT_API_KEY"))).ok()?,
-    );
+    for page in 0..MAX_ELO_PAGES {
+        let response = reqwest::Client::new()
+            .get(&url)
+            .query(&[("page", page), ("size", ELO_PAGE_SIZE)])
+            .send()
+            .await
+            .ok()?;
+        if !response.status().is_success() {
+            return None;
+        }
+        let items: Vec<serde_json::Value> = response.json().await.ok()?;
+        let page_len = items.len();
 
-    let client = reqwest::Client::new();
+        entries.extend(items.iter().filter_map(|item| {
+            Some((
+                item["matchId"].as_str()?.to_string(),
+                MatchElo {
+                    // The ELO is a string, and missing for matches before Faceit tracked it
+                    elo: item["elo"].as_str()?.parse().ok()?,
+                    date: item["date"].as_i64()? / 1000,
+                },
+            ))
+        }));
 
-    match client.get(&api_url).headers(headers).send().await {
-        Ok(response) => {
-            if response.status().is_success() {
-                match response.json::<PlayerLastMatchesResponse>().await {
-                    Ok(data) => Some(data),
-                    Err(_) => None,
-                }
-            } else {
-                None
-            }
+        let reached_oldest = items
+            .last()
+            .and_then(|item| item["date"].as_i64())
+            .is_some_and(|date| date / 1000 < oldest_needed);
+        if page_len < ELO_PAGE_SIZE || reached_oldest {
+            break;
         }
-        Err(_) => None,
+    }
+
+    Some(entries)
+}
+
+/// Reconstructs the ELO history by looking up the ELO after every match of the window
+pub async fn get_elo_history(
+    player_id: &str,
+    last_matches: &PlayerLastMatchesResponse,
+) -> Option<EloHistory> {
+    let oldest_match = last_matches
+        .items
+        .iter()
+        .map(|item| item.stats.match_finished_at as i64 / 1000)
+        .min()
+        .unwrap_or_else(|| Utc::now().timestamp());
+    let match_elo = get_match_elo(player_id, oldest_match).await?;
+
+    Some(elo_history(
+        &match_elo,
+        &last_matches.items,
+        Utc::now().timestamp(),
+    ))
+}
+
+/// Builds the ELO history from the ELO after each match, most recent first, and the matches
+/// of the window, also most recent first
+fn elo_history(match_elo: &[(String, MatchElo)], items: &[MatchItem], now: i64) -> EloHistory {
+    let elo_by_match: HashMap<&str, &MatchElo> = match_elo
+        .iter()
+        .map(|(match_id, elo)| (match_id.as_str(), elo))
+        .collect();
+    let timeline: Vec<EloPoint> = items
+        .iter()
+        .rev()
+        .filter_map(|item| {
+            let elo = elo_by_match.get(item.stats.match_id.as_str())?;
+            Some(EloPoint {
+                match_id: item.stats.match_id.clone(),
+                map: item.stats.map.clone(),
+                finished_at: elo.date,
+                elo: elo.elo,
+            })
+        })
+        .collect();
+
+    // The change is measured against the ELO after the last match before the period
+    let current = match_elo.first().map(|(_, elo)| elo.elo as i32);
+    let change = |days: i64| {
+        let period_start = now - days * 24 * 60 * 60;
+        let before = match_elo.iter().find(|(_, elo)| elo.date < period_start)?;
+        Some(current? - before.1.elo as i32)
+    };
+
+    EloHistory {
+        peak: timeline.iter().map(|point| point.elo).max(),
+        lowest: timeline.iter().map(|point| point.elo).min(),
+        change_7d: change(ELO_CHANGE_DAYS[0]),
+        change_30d: change(ELO_CHANGE_DAYS[1]),
+        change_90d: change(ELO_CHANGE_DAYS[2]),
+        timeline,
+    }
+}
+
+/// Fetch the lifetime stats of a player in a game, `cs2` or `csgo`
+/// Returns None on error
+pub async fn get_player_lifetime_stats(player_id: &str, game: &str) -> Option<FaceitLifetimeStats> {
+    let path = format!("/players/{}/stats/{}", player_id, game);
+
+    let response = api_request(&path).send().await.ok()?;
+    if !response.status().is_success() {
+        return None;
+    }
+    let json: serde_json::Value = response.json().await.ok()?;
+    let lifetime = &json["lifetime"];
+
+    Some(FaceitLifetimeStats {
+        matches: lifetime_stat(lifetime, "Matches")?,
+        wins: lifetime_stat(lifetime, "Wins").unwrap_or(0),
+        win_rate: lifetime_stat(lifetime, "Win Rate %").unwrap_or(0),
+        kd_ratio: lifetime_stat(lifetime, "Average K/D Ratio").unwrap_or(0.0),
+        headshot_percentage: lifetime_stat(lifetime, "Average Headshots %").unwrap_or(0.0),
+        longest_win_streak: lifetime_stat(lifetime, "Longest Win Streak").unwrap_or(0),
+        current_win_streak: lifetime_stat(lifetime, "Current Win Streak").unwrap_or(0),
+        recent_results: lifetime["Recent Results"]
+            .as_array()
+            .map(|results| {
+                results
+                    .iter()
+                    .map(|result| result.as_str() == Some("1"))
+                    .collect()
+            })
+            .unwrap_or_default(),
+    })
+}
+
+/// The lifetime stats are strings mostly, but not always
+fn lifetime_stat<T: FromStr>(lifetime: &serde_json::Value, key: &str) -> Option<T> {
+    match &lifetime[key] {
+        serde_json::Value::String(value) => value.trim().parse().ok(),
+        serde_json::Value::Number(value) => value.to_string().parse().ok(),
+        _ => None,
     }
 }
 
 pub fn from_api(
     data: Option<FaceitPlayerDetailsAPIResponse>,
-    last_matches: Option<PlayerLastMatchesResponse>,
+    cs2: FaceitGameResponses,
+    csgo: FaceitGameResponses,
     bans: Option<Vec<FaceitBan>>,
+    elo_history: Option<EloHistory>,
 ) -> Option<FaceitData> {
     match data {
         Some(d) => {
-            let (level, elo) = match d.games.cs2 {
-                Some(game) => (game.skill_level, game.faceit_elo),
-                None => (0, 0),
+            // Players who never played CS2 on Faceit still have their CS:GO level
+            let (level, elo, level_game) = match (&d.games.cs2, &d.games.csgo) {
+                (Some(game), _) => (game.skill_level, game.faceit_elo, Some("cs2")),
+                (None, Some(game)) => (game.skill_level, game.faceit_elo, Some("csgo")),
+                (None, None) => (0, 0, None),
             };
 
             let account_created = match DateTime::parse_from_rfc3339(&d.activated_at) {
@@ -242,161 +627,237 @@ pub fn from_api(
                 Err(_) => 0, // Default to 0 if parsing fails
             };
 
-            // Calculate aggregated stats from last matches
-            let (
-                adr,
-                wins,
-                losses,
-                win_rate,
-                kills,
-                deaths,
-                kd_ratio,
-                kr_ratio,
-                headshots,
-                headshot_percentage,
-                double_kills,
-                triple_kills,
-                quadro_kills,
-                penta_kills,
-            ) = if let Some(matches) = last_matches {
-                let mut total_adr = 0.0;
-                let mut total_wins = 0;
-                let mut total_losses = 0;
-                let mut total_kills = 0;
-                let mut total_deaths = 0;
-                let mut total_kr_ratio = 0.0;
-                let mut total_headshots = 0;
-                let mut total_double_kills = 0;
-                let mut total_triple_kills = 0;
-                let mut total_quadro_kills = 0;
-                let mut total_penta_kills = 0;
-                let mut match_count = 0;
-
-                for match_item in matches.items {
-                    let stats = match_item.stats;
-                    match_count += 1;
-
-                    // Parse ADR (Average Damage per Round)
-                    if let Ok(adr_val) = stats.adr.parse::<f32>() {
-                        total_adr += adr_val;
-                    }
-
-                    // Count wins/losses
-                    if stats.result == "1" {
-                        total_wins += 1;
-                    } else {
-                        total_losses += 1;
-                    }
-
-                    // Parse kills and deaths
-                    if let Ok(kills_val) = stats.kills.parse::<u16>() {
-                        total_kills += kills_val;
-                    }
-                    if let Ok(deaths_val) = stats.deaths.parse::<u16>() {
-                        total_deaths += deaths_val;
-                    }
+            let cs2_items = cs2.matches.map(|matches| matches.items).unwrap_or_default();
+            let career = career(cs2.lifetime.iter().chain(&csgo.lifetime));
 
-                    // Parse K/R ratio
-                    if let Ok(kr_val) = stats.kr_ratio.parse::<f32>() {
-                        total_kr_ratio += kr_val;
-                    }
-
-                    // Parse headshots
-                    if let Ok(headshots_val) = stats.headshots.parse::<u16>() {
-                        total_headshots += headshots_val;
-                    }
-
-                    // Parse multi-kills
-                    if let Ok(double_val) = stats.double_kills.parse::<u16>() {
-                        total_double_kills += double_val;
-                    }
-                    if let Ok(triple_val) = stats.triple_kills.parse::<u16>() {
-                        total_triple_kills += triple_val;
-                    }
-                    if let Ok(quadro_val) = stats.quadro_kills.parse::<u16>() {
-                        total_quadro_kills += quadro_val;
-                    }
-                    if let Ok(penta_val) = stats.penta_kills.parse::<u16>() {
-                        total_penta_kills += penta_val;
-                    }
-                }
-
-                // Calculate averages and ratios
-                let avg_adr = if match_count > 0 {
-                    total_adr / match_count as f32
-                } else {
-                    0.0
-                };
-                let win_rate = if (total_wins + total_losses) > 0 {
-                    ((total_wins as f32 / (total_wins + total_losses) as f32) * 100.0).round() as u8
-                } else {
-                    0 as u8
-                };
-                let kd_ratio = if total_deaths > 0 {
-                    total_kills as f32 / total_deaths as f32
-                } else {
-                    0.0
-                };
-                let avg_kr_ratio = if match_count > 0 {
-                    total_kr_ratio / match_count as f32
-                } else {
-                    0.0
-                };
-                let headshot_percentage = if total_kills > 0 {
-                    (total_headshots as f32 / total_kills as f32) * 100.0
-                } else {
-                    0.0
-                };
-
-                (
-                    avg_adr,
-                    total_wins,
-                    total_losses,
-                    win_rate,
-                    total_kills,
-                    total_deaths,
-                    kd_ratio,
-                    avg_kr_ratio,
-                    total_headshots,
-                    headshot_percentage,
-                    total_double_kills,
-                    total_triple_kills,
-                    total_quadro_kills,
-                    total_penta_kills,
-                )
-            } else {
-                // Default values when no match data is available
-                (0.0, 0, 0, 0, 0, 0, 0.0, 0.0, 0, 0.0, 0, 0, 0, 0)
-            };
+            let csgo = d.games.csgo.as_ref().map(|game| FaceitCsgoData {
+                level: game.skill_level,
+                elo: game.faceit_elo,
+                lifetime: csgo.lifetime,
+                stats: aggregate(csgo.matches.iter().flat_map(|matches| &matches.items)),
+            });
 
             Some(FaceitData {
                 account_created,
-                adr,
                 avatar: d.avatar,
                 bans: bans.unwrap_or_default(),
+                career,
                 country: d.country,
-                deaths,
-                double_kills,
+                csgo,
                 elo,
-                headshots,
-                headshot_percentage,
-                kd_ratio,
-                kills,
-                kr_ratio,
+                elo_history,
                 level,
-                losses,
+                level_game: level_game.map(String::from),
+                lifetime: cs2.lifetime,
+                maps: map_stats(&cs2_items),
                 nickname: d.nickname,
-                penta_kills,
-                quadro_kills,
-                triple_kills,
-                win_rate,
-                wins,
+                stats: aggregate(&cs2_items),
             })
         }
         _ => None,
     }
 }
 
+/// Combines the lifetime stats of several games, None without any
+fn career<'a>(games: impl IntoIterator<Item = &'a FaceitLifetimeStats>) -> Option<FaceitCareer> {
+    let games: Vec<&FaceitLifetimeStats> = games.into_iter().collect();
+    if games.is_empty() {
+        return None;
+    }
+
+    let matches: u32 = games.iter().map(|game| game.matches).sum();
+    let wins: u32 = games.iter().map(|game| game.wins).sum();
+    let weighted = |stat: fn(&FaceitLifetimeStats) -> f32| match matches {
+        0 => 0.0,
+        _ => {
+            games
+                .iter()
+                .map(|game| stat(game) * game.matches as f32)
+                .sum::<f32>()
+                / matches as f32
+        }
+    };
+
+    Some(FaceitCareer {
+        matches,
+        wins,
+        win_rate: match matches {
+            0 => 0,
+            _ => (wins as f32 / matches as f32 * 100.0).round() as u8,
+        },
+        kd_ratio: weighted(|game| game.kd_ratio),
+        headshot_percentage: weighted(|game| game.headshot_percentage),
+        longest_win_streak: games
+            .iter()
+            .map(|game| game.longest_win_streak)
+            .max()
+            .unwrap_or(0),
+    })
+}
+
+/// Calculate aggregated stats from matches
+fn aggregate<'a>(items: impl IntoIterator<Item = &'a MatchItem>) -> MatchAggregates {
+    let mut total_adr = 0.0;
+    let mut total_wins = 0;
+    let mut total_losses = 0;
+    let mut total_kills = 0;
+    let mut total_deaths = 0;
+    let mut total_kr_ratio = 0.0;
+    let mut total_headshots = 0;
+    let mut total_double_kills = 0;
+    let mut total_triple_kills = 0;
+    let mut total_quadro_kills = 0;
+    let mut total_penta_kills = 0;
+    let mut match_count = 0;
+
+    for match_item in items {
+        let stats = &match_item.stats;
+        match_count += 1;
+
+        // Parse ADR (Average Damage per Round)
+        if let Ok(adr_val) = stats.adr.parse::<f32>() {
+            total_adr += adr_val;
+        }
+
+        // Count wins/losses
+        if stats.result == "1" {
+            total_wins += 1;
+        } else {
+            total_losses += 1;
+        }
+
+        // Parse kills and deaths
+        if let Ok(kills_val) = stats.kills.parse::<u32>() {
+            total_kills += kills_val;
+        }
+        if let Ok(deaths_val) = stats.deaths.parse::<u32>() {
+            total_deaths += deaths_val;
+        }
+
+        // Parse K/R ratio
+        if let Ok(kr_val) = stats.kr_ratio.parse::<f32>() {
+            total_kr_ratio += kr_val;
+        }
+
+        // Parse headshots
+        if let Ok(headshots_val) = stats.headshots.parse::<u32>() {
+            total_headshots += headshots_val;
+        }
+
+        // Parse multi-kills
+        if let Ok(double_val) = stats.double_kills.parse::<u16>() {
+            total_double_kills += double_val;
+        }
+        if let Ok(triple_val) = stats.triple_kills.parse::<u16>() {
+            total_triple_kills += triple_val;
+        }
+        if let Ok(quadro_val) = stats.quadro_kills.parse::<u16>() {
+            total_quadro_kills += quadro_val;
+        }
+        if let Ok(penta_val) = stats.penta_kills.parse::<u16>() {
+            total_penta_kills += penta_val;
+        }
+    }
+
+    // Calculate averages and ratios
+    let avg_adr = if match_count > 0 {
+        total_adr / match_count as f32
+    } else {
+        0.0
+    };
+    let win_rate = if (total_wins + total_losses) > 0 {
+        ((total_wins as f32 / (total_wins + total_losses) as f32) * 100.0).round() as u8
+    } else {
+        0
+    };
+    let kd_ratio = if total_deaths > 0 {
+        total_kills as f32 / total_deaths as f32
+    } else {
+        0.0
+    };
+    let avg_kr_ratio = if match_count > 0 {
+        total_kr_ratio / match_count as f32
+    } else {
+        0.0
+    };
+    let headshot_percentage = if total_kills > 0 {
+        (total_headshots as f32 / total_kills as f32) * 100.0
+    } else {
+        0.0
+    };
+
+    MatchAggregates {
+        adr: avg_adr,
+        deaths: total_deaths,
+        double_kills: total_double_kills,
+        headshot_percentage,
+        headshots: total_headshots,
+        kd_ratio,
+        kills: total_kills,
+        kr_ratio: avg_kr_ratio,
+        losses: total_losses,
+        match_count,
+        penta_kills: total_penta_kills,
+        quadro_kills: total_quadro_kills,
+        triple_kills: total_triple_kills,
+        win_rate,
+        wins: total_wins,
+    }
+}
+
+/// Sums of the match stats of one map
+#[derive(Default)]
+struct MapTotals {
+    matches: u32,
+    wins: u32,
+    kills: u32,
+    deaths: u32,
+    headshots: u32,
+    adr: f32,
+}
+
+/// Breaks the matches down by map, most played first
+fn map_stats(items: &[MatchItem]) -> Vec<FaceitMapStats> {
+    let mut totals: HashMap<&str, MapTotals> = HashMap::new();
+
+    for item in items {
+        let stats = &item.stats;
+        let map = totals.entry(stats.map.as_str()).or_default();
+
+        map.matches += 1;
+        if stats.result == "1" {
+            map.wins += 1;
+        }
+        map.kills += stats.kills.parse::<u32>().unwrap_or(0);
+        map.deaths += stats.deaths.parse::<u32>().unwrap_or(0);
+        map.headshots += stats.headshots.parse::<u32>().unwrap_or(0);
+        map.adr += stats.adr.parse::<f32>().unwrap_or(0.0);
+    }
+
+    let mut maps: Vec<FaceitMapStats> = totals
+        .into_iter()
+        .map(|(map, totals)| FaceitMapStats {
+            map: map.to_string(),
+            matches: totals.matches,
+            win_rate: ((totals.wins as f32 / totals.matches as f32) * 100.0).round() as u8,
+            kd_ratio: if totals.deaths > 0 {
+                totals.kills as f32 / totals.deaths as f32
+            } else {
+                0.0
+            },
+            adr: totals.adr / totals.matches as f32,
+            headshot_percentage: if totals.kills > 0 {
+                (totals.headshots as f32 / totals.kills as f32) * 100.0
+            } else {
+                0.0
+            },
+        })
+        .collect();
+    maps.sort_by(|a, b| b.matches.cmp(&a.matches).then_with(|| a.map.cmp(&b.map)));
+
+    maps
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct FaceitBan {
     pub nickname: String,
@@ -414,17 +875,9 @@ pub struct FaceitBansResponse {
 }
 
 pub async fn get_player_bans(player_id: &str) -> Option<Vec<FaceitBan>> {
-    let api_url = format!("https://open.faceit.com/data/v4/players/{}/bans", player_id);
+    let path = format!("/players/{}/bans", player_id);
 
-    let mut headers = HeaderMap::new();
-    headers.insert(
-        AUTHORIZATION,
-        HeaderValue::from_str(&format!("Bearer {}", env::get("FACEIT_API_KEY"))).ok()?,
-    );
-
-    let client = reqwest::Client::new();
-
-    match client.get(&api_url).headers(headers).send().await {
+    match api_request(&path).send().await {
         Ok(response) => {
             if response.status().is_success() {
                 match response.json::<FaceitBansResponse>().await {
@@ -448,3 +901,369 @@ where
         .map(|dt| dt.timestamp())
         .map_err(serde::de::Error::custom)
 }
+
+fn parse_number_or_string<'de, D>(deserializer: D) -> Result<u8, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::Number(number) => number
+            .as_u64()
+            .and_then(|number| u8::try_from(number).ok())
+            .ok_or_else(|| serde::de::Error::custom("number out of range")),
+        serde_json::Value::String(string) => string.parse().map_err(serde::de::Error::custom),
+        other => Err(serde::de::Error::custom(format!(
+            "expected number or string, got {}",
+            other
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stub_server::{response, stub_server};
+    use std::sync::OnceLock;
+
+    /// Matches in the stubbed history, match `i` finished `i` seconds before `HISTORY_END`
+    const HISTORY_LENGTH: u32 = 250;
+    const HISTORY_END: i64 = 1_700_000_000;
+
+    /// Stands in for the Faceit data API, shared by all tests as FACEIT_API_URL is process wide
+    fn faceit_stub() {
+        static STUB: OnceLock<String> = OnceLock::new();
+        STUB.get_or_init(|| {
+            let url = stub_server(|path| {
+                let (path, query) = path.split_once('?').unwrap_or((path, ""));
+                let param = |name: &str| {
+                    query
+                        .split('&')
+                        .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+                        .and_then(|value| value.parse::<u32>().ok())
+                        .unwrap_or(0)
+                };
+                let (offset, limit) = (param("offset"), param("limit"));
+
+                match path {
+                    "/players/broken/games/cs2/stats" if offset > 0 => {
+                        response("500 Internal Server Error", "", "")
+                    }
+                    "/players/history/games/cs2/stats" | "/players/broken/games/cs2/stats" => {
+                        response(
+                            "200 OK",
+                            "Content-Type: application/json\r\n",
+                            &history_page(offset, limit),
+                        )
+                    }
+                    "/players" if query == "game=csgo&game_player_id=76561197960290418" => {
+                        response("200 OK", "Content-Type: application/json\r\n", CSGO_VETERAN)
+                    }
+                    _ => response("404 Not Found", "", ""),
+                }
+            });
+            std::env::set_var("FACEIT_API_URL", &url);
+            std::env::set_var("FACEIT_API_KEY", "key");
+            url
+        });
+    }
+
+    /// Details of a player who only played CS:GO on Faceit
+    const CSGO_VETERAN: &str = r#"{
+        "player_id": "veteran",
+        "nickname": "veteran",
+        "avatar": null,
+        "country": "de",
+        "cover_image": null,
+        "platforms": { "steam": "76561197960290418" },
+        "games": {
+            "csgo": {
+                "region": "EU",
+                "game_player_id": "76561197960290418",
+                "skill_level": 9,
+                "faceit_elo": 1850,
+                "game_player_name": "veteran"
+            }
+        },
+        "settings": { "language": "en" },
+        "friends_ids": [],
+        "new_steam_id": null,
+        "steam_id_64": "76561197960290418",
+        "steam_nickname": "veteran",
+        "memberships": [],
+        "faceit_url": "https://www.faceit.com/{lang}/players/veteran",
+        "membership_type": null,
+        "cover_featured_image": null,
+        "infractions": null,
+        "verified": false,
+        "activated_at": "2015-03-01T12:00:00Z"
+    }"#;
+
+    fn history_page(offset: u32, limit: u32) -> String {
+        let items: Vec<serde_json::Value> = (offset..(offset + limit).min(HISTORY_LENGTH))
+            .map(|index| {
+                serde_json::json!({ "stats": {
+                    "Match Id": format!("match-{}", index),
+                    "Match Finished At": (HISTORY_END - index as i64) * 1000,
+                }})
+            })
+            .collect();
+        serde_json::json!({ "start": offset, "end": offset + limit, "items": items }).to_string()
+    }
+
+    async fn last_matches(
+        player_id: &str,
+        matches: u32,
+        since: Option<i64>,
+    ) -> Option<Vec<String>> {
+        faceit_stub();
+        let window = MatchWindow { matches, since };
+        let response = get_player_last_matches(player_id, "cs2", &window).await?;
+
+        Some(
+            response
+                .items
+                .into_iter()
+                .map(|item| item.stats.match_id)
+                .collect(),
+        )
+    }
+
+    #[rocket::async_test]
+    async fn last_matches_pages_through_the_window() {
+        let matches = last_matches("history", 230, None).await.unwrap();
+
+        assert_eq!(matches.len(), 230);
+        assert_eq!(matches[0], "match-0");
+        assert_eq!(matches[100], "match-100");
+        assert_eq!(matches[229], "match-229");
+    }
+
+    #[rocket::async_test]
+    async fn last_matches_stop_at_the_end_of_the_history() {
+        let matches = last_matches("history", 1000, None).await.unwrap();
+
+        assert_eq!(matches.len(), HISTORY_LENGTH as usize);
+    }
+
+    #[rocket::async_test]
+    async fn last_matches_stop_at_the_since_cutoff() {
+        let matches = last_matches("history", 1000, Some(HISTORY_END - 149))
+            .await
+            .unwrap();
+
+        assert_eq!(matches.len(), 150);
+        assert_eq!(matches.last().map(String::as_str), Some("match-149"));
+    }
+
+    #[rocket::async_test]
+    async fn last_matches_fail_on_a_failing_page() {
+        assert!(last_matches("broken", 50, None).await.is_some());
+        assert!(last_matches("broken", 200, None).await.is_none());
+        assert!(last_matches("unknown", 20, None).await.is_none());
+    }
+
+    fn lifetime(
+        matches: u32,
+        wins: u32,
+        kd_ratio: f32,
+        longest_win_streak: u32,
+    ) -> FaceitLifetimeStats {
+        FaceitLifetimeStats {
+            matches,
+            wins,
+            win_rate: 0,
+            kd_ratio,
+            headshot_percentage: kd_ratio * 10.0,
+            longest_win_streak,
+            current_win_streak: 0,
+            recent_results: Vec::new(),
+        }
+    }
+
+    fn played(
+        map: &str,
+        result: &str,
+        kills: u32,
+        deaths: u32,
+        headshots: u32,
+        adr: f32,
+    ) -> MatchItem {
+        MatchItem {
+            stats: MatchStats {
+                map: map.to_string(),
+                result: result.to_string(),
+                kills: kills.to_string(),
+                deaths: deaths.to_string(),
+                headshots: headshots.to_string(),
+                adr: adr.to_string(),
+                ..MatchStats::default()
+            },
+        }
+    }
+
+    #[test]
+    fn map_stats_per_map_most_played_first() {
+        let maps = map_stats(&[
+            played("de_nuke", "1", 20, 10, 10, 80.0),
+            played("de_mirage", "0", 10, 20, 5, 60.0),
+            played("de_nuke", "0", 10, 20, 5, 100.0),
+            played("de_anubis", "1", 25, 0, 5, 120.0),
+            played("de_mirage", "1", 30, 10, 15, 90.0),
+            played("de_nuke", "1", 15, 15, 0, 90.0),
+            played("de_ancient", "0", 10, 10, 5, 70.0),
+            played("de_ancient", "0", 10, 10, 5, 70.0),
+        ]);
+
+        let order: Vec<(&str, u32)> = maps
+            .iter()
+            .map(|map| (map.map.as_str(), map.matches))
+            .collect();
+        // Ties on the match count are ordered by name
+        assert_eq!(
+            order,
+            vec![
+                ("de_nuke", 3),
+                ("de_ancient", 2),
+                ("de_mirage", 2),
+                ("de_anubis", 1)
+            ]
+        );
+
+        let nuke = &maps[0];
+        assert_eq!(nuke.win_rate, 67);
+        assert!((nuke.adr - 90.0).abs() < 1e-4);
+        assert!((nuke.kd_ratio - 1.0).abs() < 1e-6);
+        assert!((nuke.headshot_percentage - 33.333_332).abs() < 1e-3);
+
+        let mirage = &maps[2];
+        assert_eq!(mirage.win_rate, 50);
+        assert!((mirage.adr - 75.0).abs() < 1e-4);
+        assert!((mirage.kd_ratio - 4.0 / 3.0).abs() < 1e-6);
+
+        // Without deaths there is no K/D to speak of, the same as in the aggregates
+        let anubis = &maps[3];
+        assert_eq!(anubis.kd_ratio, 0.0);
+        assert_eq!(anubis.win_rate, 100);
+        assert!((anubis.headshot_percentage - 20.0).abs() < 1e-4);
+    }
+
+    #[rocket::async_test]
+    async fn player_details_fall_back_to_the_csgo_account() {
+        faceit_stub();
+        let details = get_player_details("76561197960290418").await.unwrap();
+        let no_game = || FaceitGameResponses {
+            matches: None,
+            lifetime: None,
+        };
+
+        let data = from_api(Some(details), no_game(), no_game(), None, None).unwrap();
+        assert_eq!(data.level, 9);
+        assert_eq!(data.elo, 1850);
+        assert_eq!(data.level_game.as_deref(), Some("csgo"));
+        assert_eq!(data.csgo.map(|csgo| csgo.level), Some(9));
+    }
+
+    #[rocket::async_test]
+    async fn player_details_without_any_account() {
+        faceit_stub();
+
+        assert!(get_player_details("76561197960290419").await.is_none());
+    }
+
+    const DAY: i64 = 24 * 60 * 60;
+
+    fn match_elo(match_id: &str, elo: u16, days_ago: i64) -> (String, MatchElo) {
+        (
+            match_id.to_string(),
+            MatchElo {
+                elo,
+                date: HISTORY_END - days_ago * DAY,
+            },
+        )
+    }
+
+    fn window_match(match_id: &str) -> MatchItem {
+        MatchItem {
+            stats: MatchStats {
+                match_id: match_id.to_string(),
+                map: "de_inferno".to_string(),
+                ..MatchStats::default()
+            },
+        }
+    }
+
+    #[test]
+    fn elo_history_timeline_is_oldest_first_and_skips_matches_without_elo() {
+        let history = elo_history(
+            &[
+                match_elo("c", 2100, 1),
+                match_elo("b", 2000, 2),
+                match_elo("a", 2050, 3),
+            ],
+            &[
+                window_match("c"),
+                window_match("untracked"),
+                window_match("a"),
+            ],
+            HISTORY_END,
+        );
+
+        let timeline: Vec<(&str, u16)> = history
+            .timeline
+            .iter()
+            .map(|point| (point.match_id.as_str(), point.elo))
+            .collect();
+        assert_eq!(timeline, vec![("a", 2050), ("c", 2100)]);
+        assert_eq!(history.timeline[0].finished_at, HISTORY_END - 3 * DAY);
+        // Match b isn't in the window, so it doesn't count for the peak and lowest
+        assert_eq!(history.peak, Some(2100));
+        assert_eq!(history.lowest, Some(2050));
+    }
+
+    #[test]
+    fn elo_history_changes_against_the_last_match_before_the_period() {
+        let history = elo_history(
+            &[
+                match_elo("d", 2100, 1),
+                match_elo("c", 2000, 5),
+                match_elo("b", 1900, 10),
+                match_elo("a", 1500, 60),
+            ],
+            &[window_match("d")],
+            HISTORY_END,
+        );
+
+        assert_eq!(history.change_7d, Some(200));
+        assert_eq!(history.change_30d, Some(600));
+        assert_eq!(history.change_90d, None);
+    }
+
+    #[test]
+    fn elo_history_without_matches() {
+        let history = elo_history(&[], &[], HISTORY_END);
+
+        assert!(history.timeline.is_empty());
+        assert_eq!(history.peak, None);
+        assert_eq!(history.change_7d, None);
+    }
+
+    #[test]
+    fn career_weights_games_by_match_count() {
+        let cs2 = lifetime(100, 50, 1.0, 4);
+        let csgo = lifetime(300, 225, 2.0, 12);
+        let career = career([&cs2, &csgo]).unwrap();
+
+        assert_eq!(career.matches, 400);
+        assert_eq!(career.wins, 275);
+        assert_eq!(career.win_rate, 69);
+        assert!((career.kd_ratio - 1.75).abs() < 1e-6);
+        assert!((career.headshot_percentage - 17.5).abs() < 1e-4);
+        assert_eq!(career.longest_win_streak, 12);
+    }
+
+    #[test]
+    fn career_without_games() {
+        assert!(career([]).is_none());
+        assert_eq!(career([&lifetime(0, 0, 0.0, 0)]).unwrap().kd_ratio, 0.0);
+    }
+}
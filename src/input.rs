@@ -0,0 +1,362 @@
+use chrono::{DateTime, NaiveDate};
+use regex::Regex;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::{sites, steam};
+
+lazy_static::lazy_static! {
+    static ref FACEIT_NICKNAME: Regex = Regex::new(r"^[A-Za-z0-9_-]{2,32}$").unwrap();
+}
+
+/// A validated lookup input. Built once from the raw user input by `ProfileInput::parse`,
+/// every resolver works on this instead of parsing the input again.
+pub enum ProfileInput {
+    Steam(SteamInput),
+    /// An s.team link that has to be followed to find the profile
+    ShortLink(Url),
+    FaceitNickname(String),
+}
+
+/// An input that names a Steam profile directly
+pub enum SteamInput {
+    /// A SteamID64 inside the valid range for individual accounts
+    Id(String),
+    /// The custom name of a https://steamcommunity.com/id/<name> url
+    Vanity(String),
+}
+
+/// How many matches are aggregated if the request doesn't ask for a window
+const DEFAULT_MATCH_COUNT: u32 = 20;
+/// Upper bound of the match window, every 100 matches cost one request to Faceit
+const MAX_MATCH_COUNT: u32 = 1000;
+
+/// The Faceit matches the stats of a player are aggregated over,
+/// from the `matches` and `since` query parameters of the player route
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct MatchWindow {
+    /// How many of the most recent matches are aggregated at most
+    pub matches: u32,
+    /// Only matches finished since this unix timestamp are aggregated
+    pub since: Option<i64>,
+}
+
+impl Default for MatchWindow {
+    fn default() -> Self {
+        MatchWindow {
+            matches: DEFAULT_MATCH_COUNT,
+            since: None,
+        }
+    }
+}
+
+impl MatchWindow {
+    /// Parses the window from the query parameters, a match count and a date like `2024-01-31`,
+    /// a year like `2024`, an RFC 3339 timestamp or a unix timestamp. With only a date every match since then is
+    /// aggregated, up to `MAX_MATCH_COUNT`.
+    pub fn parse(matches: Option<&str>, since: Option<&str>) -> Result<MatchWindow, InputError> {
+        let since = since.map(parse_date).transpose()?;
+        let matches = match matches {
+            Some(matches) => match matches.trim().parse::<u32>() {
+                Ok(count) if (1..=MAX_MATCH_COUNT).contains(&count) => count,
+                _ => return Err(InputError::InvalidMatchCount(matches.to_string())),
+            },
+            None if since.is_some() => MAX_MATCH_COUNT,
+            None => DEFAULT_MATCH_COUNT,
+        };
+
+        Ok(MatchWindow { matches, since })
+    }
+
+    /// The key the lookup result for this window is cached under, given the key of the input.
+    /// The default window keeps the plain key, so that it is shared with the lobby lookup.
+    pub fn cache_key(&self, key: &str) -> String {
+        if *self == MatchWindow::default() {
+            return key.to_string();
+        }
+
+        match self.since {
+            Some(since) => format!("{}?matches={}&since={}", key, self.matches, since),
+            None => format!("{}?matches={}", key, self.matches),
+        }
+    }
+}
+
+/// Unix timestamps have had 10 digits since 2001, long before the first Faceit match.
+/// Shorter numbers are typos or years, which would otherwise silently mean 1970.
+const MIN_TIMESTAMP_DIGITS: usize = 10;
+
+fn parse_date(date: &str) -> Result<i64, InputError> {
+    let trimmed = date.trim();
+    let digits = !trimmed.is_empty() && trimmed.bytes().all(|byte| byte.is_ascii_digit());
+
+    if digits && trimmed.len() == 4 {
+        return trimmed
+            .parse()
+            .ok()
+            .and_then(|year| NaiveDate::from_ymd_opt(year, 1, 1))
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .map(|date_time| date_time.and_utc().timestamp())
+            .ok_or_else(|| InputError::InvalidDate(date.to_string()));
+    }
+    if digits {
+        return match trimmed.parse::<i64>() {
+            Ok(timestamp) if trimmed.len() >= MIN_TIMESTAMP_DIGITS => Ok(timestamp),
+            _ => Err(InputError::InvalidDate(date.to_string())),
+        };
+    }
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(trimmed) {
+        return Ok(date_time.timestamp());
+    }
+    NaiveDate::parse_from_str(trimmed, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|date_time| date_time.and_utc().timestamp())
+        .ok_or_else(|| InputError::InvalidDate(date.to_string()))
+}
+
+/// Why an input was rejected
+#[derive(Debug)]
+pub enum InputError {
+    /// Neither a Steam ID, a Faceit nickname nor a url
+    Malformed(String),
+    /// A url of a site we don't know profiles of
+    WrongHost(String),
+    /// A url of a known site that doesn't point to a profile
+    MissingSegment(String),
+    NonNumericId(String),
+    IdOutOfRange(String),
+    InvalidInviteCode(String),
+    /// A `matches` query parameter that isn't a count in range
+    InvalidMatchCount(String),
+    /// A `since` query parameter that isn't a date
+    InvalidDate(String),
+    /// A lobby with more players than a lookup is allowed to cost
+    TooManyPlayers(usize),
+}
+
+impl InputError {
+    /// Stable, machine-readable rejection reason
+    pub fn code(&self) -> &'static str {
+        match self {
+            InputError::Malformed(_) => "malformed",
+            InputError::WrongHost(_) => "wrong_host",
+            InputError::MissingSegment(_) => "missing_segment",
+            InputError::NonNumericId(_) => "non_numeric_id",
+            InputError::IdOutOfRange(_) => "id_out_of_range",
+            InputError::InvalidInviteCode(_) => "invalid_invite_code",
+            InputError::InvalidMatchCount(_) => "invalid_match_count",
+            InputError::InvalidDate(_) => "invalid_date",
+            InputError::TooManyPlayers(_) => "too_many_players",
+        }
+    }
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputError::Malformed(input) => write!(f, "Invalid input: {}", input),
+            InputError::WrongHost(host) => write!(f, "Unsupported host: {}", host),
+            InputError::MissingSegment(url) => write!(f, "Url is not a profile url: {}", url),
+            InputError::NonNumericId(id) => write!(f, "Profile id is not numeric: {}", id),
+            InputError::IdOutOfRange(id) => {
+                write!(f, "Profile id is not a valid SteamID64: {}", id)
+            }
+            InputError::InvalidInviteCode(code) => write!(f, "Invalid invite code: {}", code),
+            InputError::InvalidMatchCount(count) => write!(
+                f,
+                "Match count must be between 1 and {}: {}",
+                MAX_MATCH_COUNT, count
+            ),
+            InputError::InvalidDate(date) => write!(f, "Invalid date: {}", date),
+            InputError::TooManyPlayers(count) => {
+                write!(f, "Too many players in the lobby: {}", count)
+            }
+        }
+    }
+}
+
+impl ProfileInput {
+    /// Accepts bare Steam IDs (SteamID64, Steam2, Steam3), Faceit nicknames, Steam profile,
+    /// vanity, invite and short link urls as well as profile urls of every site in `sites`
+    pub fn parse(input: &str) -> Result<ProfileInput, InputError> {
+        let input = input.trim();
+
+        if let Some(steam_id) = steam::parse_steam_id(input) {
+            return Ok(ProfileInput::Steam(SteamInput::Id(steam_id)));
+        }
+
+        let url = match Url::parse(input) {
+            Ok(url) => url,
+            Err(_) if !input.is_empty() && input.chars().all(|c| c.is_ascii_digit()) => {
+                return Err(InputError::IdOutOfRange(input.to_string()))
+            }
+            Err(_) if FACEIT_NICKNAME.is_match(input) => {
+                return Ok(ProfileInput::FaceitNickname(input.to_string()))
+            }
+            // Urls are often pasted without their scheme, e.g. steamcommunity.com/id/<name>
+            Err(_) if input.contains(['.', '/']) => {
+                match Url::parse(&format!("https://{}", input)) {
+                    Ok(url) => url,
+                    Err(_) => return Err(InputError::Malformed(input.to_string())),
+                }
+            }
+            Err(_) => return Err(InputError::Malformed(input.to_string())),
+        };
+
+        let host = match url.host_str() {
+            Some(host) => host.trim_start_matches("www.").to_string(),
+            None => return Err(InputError::Malformed(input.to_string())),
+        };
+        let segments: Vec<&str> = url
+            .path_segments()
+            .map(|segments| segments.filter(|segment| !segment.is_empty()).collect())
+            .unwrap_or_default();
+        let missing_segment = || InputError::MissingSegment(input.to_string());
+
+        match (host.as_str(), segments.as_slice()) {
+            ("s.team", ["p", code, ..]) => parse_invite_code(code),
+            ("s.team", [_, ..]) => Ok(ProfileInput::ShortLink(url.clone())),
+            ("steamcommunity.com", ["id", name, ..]) => {
+                Ok(ProfileInput::Steam(SteamInput::Vanity(name.to_string())))
+            }
+            ("steamcommunity.com", ["user", code, ..]) => parse_invite_code(code),
+            ("faceit.com", _) => segments
+                .iter()
+                .skip_while(|segment| **segment != "players")
+                .nth(1)
+                .map(|nickname| ProfileInput::FaceitNickname(nickname.to_string()))
+                .ok_or_else(missing_segment),
+            _ => match sites::profile_path(&host) {
+                Some(profile_path) => {
+                    let profile_path: Vec<&str> = profile_path.split('/').collect();
+                    match segments.strip_prefix(profile_path.as_slice()) {
+                        Some([id, ..]) => parse_steam_id_64(id),
+                        _ => Err(missing_segment()),
+                    }
+                }
+                None => Err(InputError::WrongHost(host)),
+            },
+        }
+    }
+}
+
+impl SteamInput {
+    /// The key the lookup result for this input is cached under
+    pub fn cache_key(&self) -> String {
+        match self {
+            SteamInput::Id(steam_id) => steam::profile_url(steam_id),
+            SteamInput::Vanity(name) => format!("https://steamcommunity.com/id/{}", name),
+        }
+    }
+}
+
+fn parse_invite_code(code: &str) -> Result<ProfileInput, InputError> {
+    steam::steam_id_from_invite_code(code)
+        .map(|steam_id| ProfileInput::Steam(SteamInput::Id(steam_id)))
+        .ok_or_else(|| InputError::InvalidInviteCode(code.to_string()))
+}
+
+fn parse_steam_id_64(id: &str) -> Result<ProfileInput, InputError> {
+    if !id.chars().all(|c| c.is_ascii_digit()) {
+        return Err(InputError::NonNumericId(id.to_string()));
+    }
+
+    steam::parse_steam_id(id)
+        .map(|steam_id| ProfileInput::Steam(SteamInput::Id(steam_id)))
+        .ok_or_else(|| InputError::IdOutOfRange(id.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn steam_id(input: &str) -> Option<String> {
+        match ProfileInput::parse(input) {
+            Ok(ProfileInput::Steam(SteamInput::Id(steam_id))) => Some(steam_id),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn parse_accepts_profile_urls_and_ids() {
+        let expected = Some("76561197960290418".to_string());
+
+        assert_eq!(steam_id("76561197960290418"), expected);
+        assert_eq!(steam_id("STEAM_0:0:12345"), expected);
+        assert_eq!(
+            steam_id("https://steamcommunity.com/profiles/76561197960290418/"),
+            expected
+        );
+        assert_eq!(
+            steam_id("https://leetify.com/app/profile/76561197960290418"),
+            expected
+        );
+    }
+
+    #[test]
+    fn parse_accepts_urls_without_scheme() {
+        assert!(matches!(
+            ProfileInput::parse("steamcommunity.com/id/gaben"),
+            Ok(ProfileInput::Steam(SteamInput::Vanity(name))) if name == "gaben"
+        ));
+        assert_eq!(
+            steam_id("www.steamcommunity.com/profiles/76561197960290418"),
+            Some("76561197960290418".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_accepts_faceit_nicknames() {
+        assert!(matches!(
+            ProfileInput::parse("s1mple"),
+            Ok(ProfileInput::FaceitNickname(nickname)) if nickname == "s1mple"
+        ));
+        assert!(matches!(
+            ProfileInput::parse("https://www.faceit.com/en/players/s1mple"),
+            Ok(ProfileInput::FaceitNickname(nickname)) if nickname == "s1mple"
+        ));
+    }
+
+    #[test]
+    fn parse_rejection_reasons() {
+        let code = |input: &str| ProfileInput::parse(input).err().map(|err| err.code());
+
+        assert_eq!(code("not a profile!"), Some("malformed"));
+        assert_eq!(code(""), Some("malformed"));
+        assert_eq!(code("   "), Some("malformed"));
+        assert_eq!(code("https://example.com/profiles/1"), Some("wrong_host"));
+        assert_eq!(code("https://www.faceit.com/en"), Some("missing_segment"));
+        assert_eq!(code("https://leetify.com/app"), Some("missing_segment"));
+        assert_eq!(
+            code("https://steamcommunity.com/profiles/gaben"),
+            Some("non_numeric_id")
+        );
+        assert_eq!(
+            code("https://steamcommunity.com/profiles/12345"),
+            Some("id_out_of_range")
+        );
+        assert_eq!(code("12345"), Some("id_out_of_range"));
+        assert_eq!(code("https://s.team/p/xyz"), Some("invalid_invite_code"));
+    }
+
+    #[test]
+    fn match_window_dates() {
+        let since = |date: &str| MatchWindow::parse(None, Some(date)).map(|window| window.since);
+
+        assert_eq!(since("2024").ok(), Some(Some(1704067200)));
+        assert_eq!(since("2024-01-01").ok(), Some(Some(1704067200)));
+        assert_eq!(
+            since("2024-01-01T01:00:00+01:00").ok(),
+            Some(Some(1704067200))
+        );
+        assert_eq!(since("1704067200").ok(), Some(Some(1704067200)));
+        assert!(matches!(since("20240"), Err(InputError::InvalidDate(_))));
+        assert!(matches!(since("17"), Err(InputError::InvalidDate(_))));
+        assert!(matches!(
+            since("yesterday"),
+            Err(InputError::InvalidDate(_))
+        ));
+    }
+}
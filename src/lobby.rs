@@ -0,0 +1,206 @@
+use regex::Regex;
+use rocket::futures::future::join_all;
+use serde::Serialize;
+
+use crate::error::LookupError;
+use crate::input::InputError;
+use crate::{lookup, steam, tracking};
+
+/// How many players a lobby lookup accepts at most, every player costs a full lookup
+const MAX_LOBBY_PLAYERS: usize = 32;
+
+lazy_static::lazy_static! {
+    static ref STEAM_ID: Regex =
+        Regex::new(r"\[U:1:\d+\]|STEAM_[0-5]:[01]:\d+|\b7656119\d{10}\b").unwrap();
+    // Up to the last matching quote of the line, names may contain the other kind of quote
+    static ref NAME: Regex = Regex::new(r#""(.*)"|'(.*)'"#).unwrap();
+    static ref TEAM_HEADER: Regex = Regex::new(
+        r"(?i)^[\s#=-]*(counter-terrorists?|terrorists?|ct|t|spectators?)[\s#=:-]*$"
+    )
+    .unwrap();
+}
+
+/// A player found in the output of the `status` console command
+pub struct StatusEntry {
+    pub steam_id: String,
+    pub name: Option<String>,
+    pub team: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Lobby {
+    pub teams: Vec<LobbyTeam>,
+    /// SteamID64s of the players whose lookup failed
+    pub failed: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct LobbyTeam {
+    /// CT, T or Spectator, None if the status output doesn't tell
+    pub name: Option<String>,
+    pub players: Vec<LobbyPlayer>,
+}
+
+#[derive(Serialize)]
+pub struct LobbyPlayer {
+    /// In-game name as shown in the status output
+    pub name: Option<String>,
+    pub player: serde_json::Value,
+}
+
+/// Looks up every player of a lobby from the pasted output of the CS2 console `status` command
+pub async fn lookup_lobby(status: &str) -> Result<Lobby, LookupError> {
+    let entries = parse_status(status);
+    tracking::track_lobby_request(entries.len()).await;
+
+    if entries.len() > MAX_LOBBY_PLAYERS {
+        return Err(InputError::TooManyPlayers(entries.len()).into());
+    }
+
+    let players = join_all(
+        entries
+            .iter()
+            .map(|entry| lookup::lookup_steam_id(&entry.steam_id)),
+    )
+    .await;
+
+    Ok(group_players(entries, players))
+}
+
+/// Groups the looked up players by team, in the order of the status output
+fn group_players(entries: Vec<StatusEntry>, players: Vec<Result<String, LookupError>>) -> Lobby {
+    let mut teams: Vec<LobbyTeam> = Vec::new();
+    let mut failed = Vec::new();
+
+    for (entry, json) in entries.into_iter().zip(players) {
+        let player = match json.map(|json| serde_json::from_str::<serde_json::Value>(&json)) {
+            Ok(Ok(player)) => player,
+            _ => {
+                failed.push(entry.steam_id);
+                continue;
+            }
+        };
+
+        let player = LobbyPlayer {
+            name: entry.name,
+            player,
+        };
+
+        match teams.iter_mut().find(|team| team.name == entry.team) {
+            Some(team) => team.players.push(player),
+            None => teams.push(LobbyTeam {
+                name: entry.team,
+                players: vec![player],
+            }),
+        }
+    }
+
+    Lobby { teams, failed }
+}
+
+/// Parses every player with a Steam ID out of the raw text of the CS2 console `status` command.
+/// Players listed below a team header line (e.g. `---- CT ----`) are assigned to that team.
+/// Bots and duplicate entries are skipped.
+pub fn parse_status(status: &str) -> Vec<StatusEntry> {
+    let mut entries: Vec<StatusEntry> = Vec::new();
+    let mut team = None;
+
+    for line in status.lines() {
+        if let Some(captures) = TEAM_HEADER.captures(line) {
+            team = Some(team_name(&captures[1]).to_string());
+            continue;
+        }
+
+        let steam_id = match STEAM_ID
+            .find(line)
+            .and_then(|id| steam::parse_steam_id(id.as_str()))
+        {
+            Some(steam_id) => steam_id,
+            None => continue,
+        };
+
+        if entries.iter().any(|entry| entry.steam_id == steam_id) {
+            continue;
+        }
+
+        entries.push(StatusEntry {
+            steam_id,
+            name: NAME
+                .captures(line)
+                .and_then(|captures| captures.get(1).or_else(|| captures.get(2)))
+                .map(|name| name.as_str().to_string()),
+            team: team.clone(),
+        });
+    }
+
+    entries
+}
+
+fn team_name(header: &str) -> &'static str {
+    match header.to_lowercase().as_str() {
+        "ct" | "counter-terrorist" | "counter-terrorists" => "CT",
+        "t" | "terrorist" | "terrorists" => "T",
+        _ => "Spectator",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_status_keeps_quotes_inside_names() {
+        let entries = parse_status(
+            "# 2 1 \"Bob's\" STEAM_1:0:12345 02:10 40 0 active\n\
+             # 3 2 'Say \"hi\"' [U:1:24691] 02:10 40 0 active",
+        );
+
+        assert_eq!(entries[0].name.as_deref(), Some("Bob's"));
+        assert_eq!(entries[1].name.as_deref(), Some("Say \"hi\""));
+    }
+
+    #[test]
+    fn parse_status_assigns_teams_and_skips_duplicates() {
+        let entries = parse_status(
+            "---- CT ----\n\
+             # 2 1 \"a\" STEAM_1:0:12345\n\
+             ---- T ----\n\
+             # 3 2 \"b\" [U:1:24691]\n\
+             # 4 3 \"a again\" [U:1:24690]\n\
+             # 5 BOT \"bot\" BOT",
+        );
+
+        let teams: Vec<(&str, Option<&str>)> = entries
+            .iter()
+            .map(|entry| (entry.steam_id.as_str(), entry.team.as_deref()))
+            .collect();
+        assert_eq!(
+            teams,
+            vec![
+                ("76561197960290418", Some("CT")),
+                ("76561197960290419", Some("T")),
+            ]
+        );
+    }
+
+    #[test]
+    fn group_players_by_team_and_reports_failures() {
+        let entry = |steam_id: &str, team: &str| StatusEntry {
+            steam_id: steam_id.to_string(),
+            name: None,
+            team: Some(team.to_string()),
+        };
+        let lobby = group_players(
+            vec![entry("1", "CT"), entry("2", "T"), entry("3", "CT")],
+            vec![
+                Ok("{}".to_string()),
+                Err(LookupError::PlayerNotFound("2".to_string())),
+                Ok("{}".to_string()),
+            ],
+        );
+
+        assert_eq!(lobby.failed, vec!["2".to_string()]);
+        assert_eq!(lobby.teams.len(), 1);
+        assert_eq!(lobby.teams[0].players.len(), 2);
+    }
+}
@@ -0,0 +1,580 @@
+use rocket::futures::future::join_all;
+use rocket::tokio;
+use serde::Serialize;
+use std::cmp::Reverse;
+
+use crate::account_age::{self, AccountCreated};
+use crate::error::LookupError;
+use crate::input::{InputError, MatchWindow, ProfileInput, SteamInput};
+use crate::sites::Site;
+use crate::steam::{
+    Availability, FriendsAnalysis, LibrarySummary, NameHistoryEntry, OwnedGame, SteamBadges,
+    SteamGameStats, SteamIdentity, SteamProfile, VacBanInfo,
+};
+use crate::{faceit, redis, sites, steam, tracking};
+
+#[derive(Serialize)]
+pub struct Player {
+    steam_id: String,
+    faceit_data: Option<faceit::FaceitData>,
+    steam_game_stats: Option<SteamGameStats>,
+    cs2_hours: Option<u32>,
+    /// CS2 hours of the last two weeks according to the game library
+    cs2_hours_2weeks: Option<u32>,
+    /// CS2 hours of the last two weeks according to the recently played games
+    cs2_recent_hours: Option<u32>,
+    account_created: Option<i64>,
+    /// True if the profile hides its creation date and `account_created` is estimated from the SteamID
+    account_created_estimated: bool,
+    vac_ban_info: Option<VacBanInfo>,
+    steam_profile: Option<SteamProfile>,
+    /// Former persona names, most recent first
+    name_history: Option<Vec<NameHistoryEntry>>,
+    friends: Option<FriendsAnalysis>,
+    steam_level: Option<u32>,
+    steam_badges: Option<SteamBadges>,
+    library: Option<LibrarySummary>,
+    sites: Vec<Site>,
+    /// The Faceit matches the stats are aggregated over
+    match_window: MatchWindow,
+    availability: PlayerAvailability,
+}
+
+/// Why the Steam fields of a player are there or not, None alone can mean
+/// a private profile, a failing Steam API or zero hours
+#[derive(Serialize)]
+struct PlayerAvailability {
+    steam_profile: Availability,
+    account_created: Availability,
+    cs2_hours: Availability,
+    cs2_recent_hours: Availability,
+    vac_ban_info: Availability,
+    steam_game_stats: Availability,
+    name_history: Availability,
+    friends: Availability,
+    steam_level: Availability,
+    steam_badges: Availability,
+    library: Availability,
+}
+
+/// How many candidates a search returns at most
+const SEARCH_RESULT_LIMIT: usize = 10;
+
+/// A candidate for the search box autocomplete
+#[derive(Serialize)]
+pub struct SearchResult {
+    nickname: String,
+    avatar: Option<String>,
+    country: Option<String>,
+    skill_level: Option<u8>,
+    steam_id: Option<String>,
+}
+
+/// Looks up a player from any input `ProfileInput` accepts, with the Faceit stats aggregated over the window.
+/// Returns the player as JSON, from the cache if it has been looked up recently.
+pub async fn lookup_player(url: &str, window: MatchWindow) -> Result<String, LookupError> {
+    tracking::track_search_request(url).await;
+    let input = tracked(resolve_input(url).await).await?;
+    let cache_key = window.cache_key(&input.cache_key());
+
+    match tracked(redis::get(&cache_key).map_err(LookupError::Cache)).await? {
+        Some(data) => {
+            tracking::track_cache_hit(&cache_key).await;
+            Ok(data)
+        }
+        None => {
+            let steam_id = match input {
+                SteamInput::Vanity(name) => {
+                    tracked(steam::get_steam_id_from_vanity_name(&name).await).await?
+                }
+                SteamInput::Id(steam_id) => steam_id,
+            };
+            tracked(handle_new_player(&steam_id, &cache_key, window).await).await
+        }
+    }
+}
+
+/// Resolves any input `ProfileInput` accepts to all representations of the Steam account
+pub async fn lookup_identity(url: &str) -> Result<SteamIdentity, LookupError> {
+    tracking::track_steam_id_request(url).await;
+    let input = tracked(resolve_input(url).await).await?;
+
+    let (steam_id, vanity_name) = match input {
+        SteamInput::Vanity(name) => (
+            tracked(steam::get_steam_id_from_vanity_name(&name).await).await?,
+            Some(name),
+        ),
+        SteamInput::Id(steam_id) => {
+            let vanity_name = steam::get_player_summary(&steam_id)
+                .await
+                .ok()
+                .flatten()
+                .and_then(|profile| profile.vanity_name());
+            (steam_id, vanity_name)
+        }
+    };
+
+    let identity = steam::identity(&steam_id, vanity_name)
+        .ok_or_else(|| LookupError::PlayerNotFound(steam_id.clone()));
+    tracked(identity).await
+}
+
+/// Everything fetched from the Faceit API for a player
+struct FaceitResponses {
+    details: Option<faceit::FaceitPlayerDetailsAPIResponse>,
+    cs2: faceit::FaceitGameResponses,
+    csgo: faceit::FaceitGameResponses,
+    bans: Option<Vec<faceit::FaceitBan>>,
+    elo_history: Option<faceit::EloHistory>,
+}
+
+/// Everything fetched from the Steam API for a player
+struct SteamData {
+    owned_games: Result<Option<Vec<OwnedGame>>, LookupError>,
+    cs2_recent_hours: Result<Option<u32>, LookupError>,
+    profile: Result<Option<SteamProfile>, LookupError>,
+    vac_ban_info: Result<Option<VacBanInfo>, LookupError>,
+    game_stats: Result<Option<SteamGameStats>, LookupError>,
+    name_history: Result<Option<Vec<NameHistoryEntry>>, LookupError>,
+    friends: Result<Option<FriendsAnalysis>, LookupError>,
+    level: Result<Option<u32>, LookupError>,
+    badges: Result<Option<SteamBadges>, LookupError>,
+}
+
+/// Searches Faceit players by nickname for the search box autocomplete.
+/// Exact matches are ranked first, then nicknames starting with the query,
+/// each ordered by skill level. Results are cached for a few minutes.
+pub async fn search(query: &str) -> Result<String, LookupError> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Err(InputError::Malformed(query.to_string()).into());
+    }
+
+    let cache_key = format!("search:{}", query.to_lowercase());
+    if let Some(data) = tracked(redis::get(&cache_key).map_err(LookupError::Cache)).await? {
+        return Ok(data);
+    }
+
+    let mut items = tracked(faceit::search_players(query).await).await?;
+    rank_candidates(&mut items, query);
+    items.truncate(SEARCH_RESULT_LIMIT);
+
+    // The search doesn't contain the SteamID64, so the details of every candidate are needed
+    let details = join_all(
+        items
+            .iter()
+            .map(|item| faceit::get_player_details_by_id(&item.player_id)),
+    )
+    .await;
+    // Every candidate has a SteamID64 on Faceit, a missing one means its details call failed
+    let complete = details.iter().all(Option::is_some);
+
+    let results: Vec<SearchResult> = items
+        .into_iter()
+        .zip(details)
+        .map(|(item, details)| SearchResult {
+            skill_level: item.cs2_skill_level(),
+            nickname: item.nickname,
+            avatar: item.avatar.filter(|avatar| !avatar.is_empty()),
+            country: item.country,
+            steam_id: details.map(|details| details.steam_id_64),
+        })
+        .collect();
+
+    let json =
+        serde_json::to_string(&results).map_err(|e| LookupError::Serialization(e.to_string()))?;
+
+    // Results with failed details calls aren't cached, or the SteamIDs would stay missing
+    if complete {
+        let cached = redis::set(&cache_key, &json).and_then(|_| redis::expire(&cache_key, 60 * 5)); // 5 minutes
+        if let Err(e) = cached {
+            tracking::track_error(&LookupError::Cache(e).to_string()).await;
+        }
+    }
+
+    Ok(json)
+}
+
+/// Orders the candidates of a search, exact matches first, then nicknames starting with
+/// the query, each by skill level
+fn rank_candidates(items: &mut [faceit::FaceitSearchItem], query: &str) {
+    let lowercase_query = query.to_lowercase();
+    items.sort_by_key(|item| {
+        let nickname = item.nickname.to_lowercase();
+        (
+            nickname != lowercase_query,
+            !nickname.starts_with(&lowercase_query),
+            Reverse(item.cs2_skill_level()),
+        )
+    });
+}
+
+/// Looks up a player by SteamID64, using the cached response if there is one
+pub async fn lookup_steam_id(steam_id: &str) -> Result<String, LookupError> {
+    let profile_url = steam::profile_url(steam_id);
+
+    match tracked(redis::get(&profile_url).map_err(LookupError::Cache)).await? {
+        Some(data) => {
+            tracking::track_cache_hit(&profile_url).await;
+            Ok(data)
+        }
+        None => {
+            tracked(handle_new_player(steam_id, &profile_url, MatchWindow::default()).await).await
+        }
+    }
+}
+
+/// Reports the error of a failed lookup step to tracking and passes the result on
+async fn tracked<T>(result: Result<T, LookupError>) -> Result<T, LookupError> {
+    if let Err(err) = &result {
+        tracking::track_error(&err.to_string()).await;
+    }
+
+    result
+}
+
+/// Parses the input and resolves the inputs that don't name a Steam profile directly
+/// (short links, Faceit nicknames) into one that does
+async fn resolve_input(url: &str) -> Result<SteamInput, LookupError> {
+    let input = match ProfileInput::parse(url)? {
+        ProfileInput::ShortLink(link) => {
+            ProfileInput::parse(&steam::resolve_short_link(&link).await?)?
+        }
+        input => input,
+    };
+
+    match input {
+        ProfileInput::Steam(steam_input) => Ok(steam_input),
+        ProfileInput::FaceitNickname(nickname) => {
+            let player_details = faceit::get_player_details_by_nickname(&nickname).await?;
+            Ok(SteamInput::Id(player_details.steam_id_64))
+        }
+        ProfileInput::ShortLink(link) => Err(LookupError::PlayerNotFound(link.to_string())),
+    }
+}
+
+async fn handle_new_player(
+    steam_id: &str,
+    url: &str,
+    window: MatchWindow,
+) -> Result<String, LookupError> {
+    let (faceit_responses, steam_data) = tokio::join!(
+        fetch_faceit_data(steam_id, &window),
+        fetch_steam_data(steam_id),
+    );
+    // The estimate only needs the SteamID, so a failed summary call still gets one
+    let account_created = match &steam_data.profile {
+        Ok(Some(profile)) => account_age::account_created(steam_id, profile.time_created).await,
+        Err(_) => account_age::account_created(steam_id, None).await,
+        Ok(None) => None,
+    };
+
+    let player = create_player(
+        steam_id,
+        faceit_responses,
+        steam_data,
+        account_created,
+        window,
+    );
+    let json =
+        serde_json::to_string(&player).map_err(|e| LookupError::Serialization(e.to_string()))?;
+
+    // A failing cache shouldn't fail the lookup, the player is just fetched again next time
+    let cached = redis::set(url, &json).and_then(|_| redis::expire(url, 60 * 60 * 24)); // 1 day
+    if let Err(e) = cached {
+        tracking::track_error(&LookupError::Cache(e).to_string()).await;
+    }
+
+    Ok(json)
+}
+
+async fn fetch_faceit_data(steam_id: &str, window: &MatchWindow) -> FaceitResponses {
+    let details = faceit::get_player_details(steam_id).await;
+    let no_game = || faceit::FaceitGameResponses {
+        matches: None,
+        lifetime: None,
+    };
+
+    // Only get last matches, ELO history and bans if we have valid faceit data
+    let Some(player_details) = &details else {
+        return FaceitResponses {
+            details: None,
+            cs2: no_game(),
+            csgo: no_game(),
+            bans: None,
+            elo_history: None,
+        };
+    };
+    let player_id = &player_details.player_id;
+
+    let (cs2_matches, cs2_lifetime, csgo, bans) = tokio::join!(
+        faceit::get_player_last_matches(player_id, "cs2", window),
+        faceit::get_player_lifetime_stats(player_id, "cs2"),
+        async {
+            match player_details.games.csgo {
+                Some(_) => {
+                    let (matches, lifetime) = tokio::join!(
+                        faceit::get_player_last_matches(player_id, "csgo", window),
+                        faceit::get_player_lifetime_stats(player_id, "csgo"),
+                    );
+                    faceit::FaceitGameResponses { matches, lifetime }
+                }
+                None => no_game(),
+            }
+        },
+        faceit::get_player_bans(player_id),
+    );
+    let cs2 = faceit::FaceitGameResponses {
+        matches: cs2_matches,
+        lifetime: cs2_lifetime,
+    };
+
+    // The ELO history is reconstructed from the matches, so it has to wait for them
+    let elo_history = match &cs2.matches {
+        Some(matches) => faceit::get_elo_history(player_id, matches).await,
+        None => None,
+    };
+
+    FaceitResponses {
+        details,
+        cs2,
+        csgo,
+        bans,
+        elo_history,
+    }
+}
+
+async fn fetch_steam_data(steam_id: &str) -> SteamData {
+    let (owned_games, cs2_recent_hours, profile, vac_ban_info, name_history, level, badges) = tokio::join!(
+        steam::get_owned_games(steam_id),
+        steam::get_cs2_recent_hours(steam_id),
+        steam::get_player_summary(steam_id),
+        steam::get_vac_ban_info(steam_id),
+        steam::get_name_history(steam_id),
+        steam::get_steam_level(steam_id),
+        steam::get_badges(steam_id),
+    );
+
+    // Private profiles never have game stats or a friend list, so don't bother asking for them
+    let (game_stats, friends) = match &profile {
+        Ok(Some(profile)) if profile.is_public() => tokio::join!(
+            steam::get_game_stats(steam_id),
+            steam::get_friends_analysis(steam_id),
+        ),
+        _ => (Ok(None), Ok(None)),
+    };
+
+    SteamData {
+        owned_games,
+        cs2_recent_hours,
+        profile,
+        vac_ban_info,
+        game_stats,
+        name_history,
+        friends,
+        level,
+        badges,
+    }
+}
+
+fn create_player(
+    steam_id: &str,
+    faceit_responses: FaceitResponses,
+    steam_data: SteamData,
+    account_created: Option<AccountCreated>,
+    window: MatchWindow,
+) -> Player {
+    let sites = sites::links(
+        steam_id,
+        faceit_responses
+            .details
+            .as_ref()
+            .map(|data| data.faceit_url.as_str()),
+    );
+    let availability = availability(&steam_data);
+    let profile = steam_data.profile.ok().flatten();
+    let owned_games = steam_data.owned_games.ok().flatten();
+    let library = owned_games
+        .as_ref()
+        .map(|games| steam::get_library_summary(games));
+    let owned_games = owned_games.unwrap_or_default();
+
+    Player {
+        steam_id: steam_id.to_string(),
+        faceit_data: faceit::from_api(
+            faceit_responses.details,
+            faceit_responses.cs2,
+            faceit_responses.csgo,
+            faceit_responses.bans,
+            faceit_responses.elo_history,
+        ),
+        steam_game_stats: steam_data.game_stats.ok().flatten(),
+        cs2_hours: steam::get_cs2_hours(&owned_games),
+        cs2_hours_2weeks: steam::get_cs2_hours_2weeks(&owned_games),
+        cs2_recent_hours: steam_data.cs2_recent_hours.ok().flatten(),
+        account_created: account_created.map(|created| created.timestamp),
+        account_created_estimated: account_created.is_some_and(|created| created.estimated),
+        vac_ban_info: steam_data.vac_ban_info.ok().flatten(),
+        steam_profile: profile,
+        name_history: steam_data.name_history.ok().flatten(),
+        friends: steam_data.friends.ok().flatten(),
+        steam_level: steam_data.level.ok().flatten(),
+        steam_badges: steam_data.badges.ok().flatten(),
+        library,
+        sites,
+        match_window: window,
+        availability,
+    }
+}
+
+fn availability(steam_data: &SteamData) -> PlayerAvailability {
+    let steam_profile = Availability::of(&steam_data.profile);
+    let is_public = matches!(&steam_data.profile, Ok(Some(profile)) if profile.is_public());
+
+    // Steam leaves out what the privacy settings hide, unless the profile itself is missing
+    // or couldn't be fetched, which then explains the gap instead
+    let hidden = match steam_profile {
+        Availability::Available | Availability::Private => Availability::Private,
+        missing => missing,
+    };
+    let owns_cs2 = match &steam_data.owned_games {
+        Ok(Some(games)) => Some(steam::get_cs2_hours(games).is_some()),
+        _ => None,
+    };
+
+    // Steam only shows the creation date of public profiles
+    let account_created = match &steam_data.profile {
+        Ok(Some(profile)) if profile.time_created.is_some() => Availability::Available,
+        Ok(Some(_)) if !is_public => Availability::Private,
+        Ok(Some(_)) => Availability::NotFound,
+        _ => steam_profile,
+    };
+
+    // A library can be hidden even on a public profile, which Steam answers with no games at all
+    let library = Availability::of_hideable(&steam_data.owned_games, hidden);
+    let cs2_hours = match owns_cs2 {
+        Some(false) => Availability::NotFound,
+        _ => library,
+    };
+
+    // Accounts without CS2 have no stats, Steam answers them like hidden game details
+    let steam_game_stats = match (&steam_data.game_stats, owns_cs2) {
+        (Ok(None), Some(false)) => Availability::NotFound,
+        (stats, _) => Availability::of_hideable(stats, hidden),
+    };
+
+    PlayerAvailability {
+        steam_profile,
+        account_created,
+        cs2_hours,
+        cs2_recent_hours: Availability::of_hideable(&steam_data.cs2_recent_hours, hidden),
+        vac_ban_info: Availability::of(&steam_data.vac_ban_info),
+        steam_game_stats,
+        name_history: Availability::of_hideable(&steam_data.name_history, hidden),
+        friends: Availability::of_hideable(&steam_data.friends, hidden),
+        steam_level: Availability::of_hideable(&steam_data.level, hidden),
+        steam_badges: Availability::of_hideable(&steam_data.badges, hidden),
+        library,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn steam_data(profile: Result<Option<SteamProfile>, LookupError>) -> SteamData {
+        SteamData {
+            owned_games: Ok(None),
+            cs2_recent_hours: Ok(None),
+            profile,
+            vac_ban_info: Ok(None),
+            game_stats: Ok(None),
+            name_history: Ok(None),
+            friends: Ok(None),
+            level: Ok(None),
+            badges: Ok(None),
+        }
+    }
+
+    fn profile(visibility: u8) -> Option<SteamProfile> {
+        serde_json::from_value(serde_json::json!({
+            "personaname": "name",
+            "profileurl": "https://steamcommunity.com/id/name/",
+            "avatar": "",
+            "avatarmedium": "",
+            "avatarfull": "",
+            "communityvisibilitystate": visibility,
+        }))
+        .ok()
+    }
+
+    fn candidate(nickname: &str, skill_level: Option<u8>) -> faceit::FaceitSearchItem {
+        faceit::FaceitSearchItem {
+            player_id: nickname.to_string(),
+            nickname: nickname.to_string(),
+            avatar: None,
+            country: None,
+            games: skill_level
+                .map(|skill_level| faceit::FaceitSearchGame {
+                    name: "cs2".to_string(),
+                    skill_level,
+                })
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn rank_candidates_by_match_then_skill_level() {
+        let mut items = vec![
+            candidate("xsimple", Some(10)),
+            candidate("Simple2", Some(3)),
+            candidate("simple_", Some(8)),
+            candidate("SIMPLE", Some(1)),
+            candidate("simpleton", None),
+        ];
+        rank_candidates(&mut items, "Simple");
+
+        let nicknames: Vec<&str> = items.iter().map(|item| item.nickname.as_str()).collect();
+        assert_eq!(
+            nicknames,
+            vec!["SIMPLE", "simple_", "Simple2", "simpleton", "xsimple"]
+        );
+    }
+
+    #[test]
+    fn availability_of_private_profile() {
+        let availability = availability(&steam_data(Ok(profile(1))));
+
+        assert!(availability.steam_profile == Availability::Available);
+        assert!(availability.account_created == Availability::Private);
+        assert!(availability.cs2_hours == Availability::Private);
+        assert!(availability.friends == Availability::Private);
+        assert!(availability.steam_level == Availability::Private);
+        assert!(availability.vac_ban_info == Availability::NotFound);
+    }
+
+    #[test]
+    fn availability_carries_upstream_errors_of_the_profile() {
+        let availability = availability(&steam_data(Err(LookupError::SteamApi(String::new()))));
+
+        assert!(availability.steam_profile == Availability::UpstreamError);
+        assert!(availability.cs2_hours == Availability::UpstreamError);
+        assert!(availability.library == Availability::UpstreamError);
+        assert!(availability.steam_game_stats == Availability::UpstreamError);
+    }
+
+    #[test]
+    fn availability_of_public_profile_without_cs2() {
+        let mut data = steam_data(Ok(profile(3)));
+        data.owned_games = Ok(Some(Vec::new()));
+        data.level = Err(LookupError::SteamApi(String::new()));
+        let availability = availability(&data);
+
+        assert!(availability.library == Availability::Available);
+        assert!(availability.cs2_hours == Availability::NotFound);
+        assert!(availability.steam_game_stats == Availability::NotFound);
+        assert!(availability.steam_level == Availability::UpstreamError);
+        assert!(availability.friends == Availability::Private);
+    }
+}
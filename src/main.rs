@@ -1,37 +1,59 @@
 #[macro_use]
 extern crate rocket;
 
+use lobby::Lobby;
 use rocket::fs::{relative, FileServer, NamedFile};
-use serde::Serialize;
+use rocket::serde::json::Json;
 
+use error::LookupError;
+use input::MatchWindow;
 use rocket::fairing::{Fairing, Info, Kind};
 use rocket::http::Header;
+use rocket::response::content::RawJson;
+use rocket::response::{self, Responder};
 use rocket::{Request, Response};
-use steam::VacBanInfo;
+use steam::SteamIdentity;
 
+mod account_age;
 mod cors;
 mod env;
+mod error;
 mod faceit;
+mod input;
+mod lobby;
+mod lookup;
 mod redis;
+mod sites;
 mod steam;
+#[cfg(test)]
+mod stub_server;
 mod tracking;
 
 pub struct CacheFairing;
 
-#[derive(Serialize)]
-struct Player {
-    steam_id: String,
-    faceit_data: Option<faceit::FaceitData>,
-    cs2_hours: Option<u32>,
-    account_created: Option<i64>,
-    vac_ban_info: Option<VacBanInfo>,
-    sites: Vec<Site>,
-}
+/// When the legacy /player mount was deprecated, as a unix timestamp (2026-10-18)
+const LEGACY_DEPRECATED_AT: i64 = 1792281600;
+/// When the legacy /player mount is going to be removed
+const LEGACY_SUNSET: &str = "Sun, 18 Apr 2027 00:00:00 GMT";
+
+/// Wraps a response of the legacy /player mount with headers announcing
+/// its deprecation (RFC 9745) and removal (RFC 8594) in favor of /api/v1/player
+struct Deprecated<R>(R);
+
+impl<'r, R: Responder<'r, 'static>> Responder<'r, 'static> for Deprecated<R> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        // The same resource under /api/v1, e.g. /player/<url> is replaced by /api/v1/player/<url>
+        let successor = format!(
+            "</api/v1{}>; rel=\"successor-version\"",
+            request.uri().path()
+        );
 
-#[derive(Serialize)]
-struct Site {
-    url: String,
-    title: String,
+        Response::build_from(self.0.respond_to(request)?)
+            .raw_header("Deprecation", format!("@{}", LEGACY_DEPRECATED_AT))
+            .raw_header("Sunset", LEGACY_SUNSET)
+            .raw_header("Link", successor)
+            .ok()
+    }
 }
 
 #[rocket::async_trait]
@@ -79,6 +101,9 @@ fn rocket() -> _ {
             .attach(cors::Cors)
             .attach(CacheFairing)
             .mount("/api/v1/player", routes![player_route, all_options])
+            .mount("/api/v1/lobby", routes![lobby_route, all_options])
+            .mount("/api/v1/steamid", routes![steam_id_route, all_options])
+            .mount("/api/v1/search", routes![search_route, all_options])
             .mount("/player", routes![old_player_route, all_options])
             .mount("/", FileServer::from("/www/public"))
             .register("/", catchers![default_catch]),
@@ -86,170 +111,77 @@ fn rocket() -> _ {
             .attach(cors::Cors)
             .attach(CacheFairing)
             .mount("/api/v1/player", routes![player_route, all_options])
+            .mount("/api/v1/lobby", routes![lobby_route, all_options])
+            .mount("/api/v1/steamid", routes![steam_id_route, all_options])
+            .mount("/api/v1/search", routes![search_route, all_options])
             .mount("/player", routes![old_player_route, all_options])
             .mount("/", FileServer::from(relative!("frontend/dist")))
             .register("/", catchers![default_catch]),
     }
 }
 
-#[get("/<url>")]
-async fn player_route(url: &str) -> Result<String, String> {
-    tracking::track_search_request(&url).await;
-    let normalized_url = steam::normalize_url(url)?;
-
-    match redis::get(&normalized_url) {
-        Some(data) => {
-            tracking::track_cache_hit(&normalized_url).await;
-            Ok(data)
-        }
-        None => match steam::is_vanity_url(&normalized_url) {
-            true => match steam::get_steam_id_from_vanity_url(&normalized_url).await {
-                Some(steam_id) => Ok(handle_new_player(&steam_id, &normalized_url).await),
-                None => {
-                    let msg = format!(
-                        "Could not resolve steam id from vanity URL: {}",
-                        normalized_url
-                    );
-                    tracking::track_error(&msg).await;
-                    Err(msg)
-                }
-            },
-            false => match steam::get_steam_id_from_non_vanity_url(&normalized_url) {
-                Some(steam_id) => Ok(handle_new_player(&steam_id, &normalized_url).await),
-                None => {
-                    let msg = format!(
-                        "Could not resolve steam id from profile url: {}",
-                        normalized_url
-                    );
-                    tracking::track_error(&msg).await;
-                    Err(msg)
-                }
-            },
-        },
-    }
+/// Looks up a player, with the Faceit stats aggregated over the last `matches` matches and/or
+/// the matches since the date `since`
+#[get("/<url>?<matches>&<since>")]
+async fn player_route(
+    url: &str,
+    matches: Option<&str>,
+    since: Option<&str>,
+) -> Result<String, LookupError> {
+    let window = MatchWindow::parse(matches, since)?;
+    lookup::lookup_player(url, window).await
 }
 
+/// The player lookup before it was versioned under /api/v1, kept alive for old extension versions
 #[get("/<url>")]
-async fn old_player_route(url: &str) -> Result<String, String> {
-    tracking::track_search_request(&url).await;
-    let normalized_url = steam::normalize_url(url)?;
-
-    match redis::get(&normalized_url) {
-        Some(data) => {
-            tracking::track_cache_hit(&normalized_url).await;
-            Ok(data)
-        }
-        None => match steam::is_vanity_url(&normalized_url) {
-            true => match steam::get_steam_id_from_vanity_url(&normalized_url).await {
-                Some(steam_id) => Ok(handle_new_player(&steam_id, &normalized_url).await),
-                None => {
-                    let msg = format!(
-                        "Could not resolve steam id from vanity URL: {}",
-                        normalized_url
-                    );
-                    tracking::track_error(&msg).await;
-                    Err(msg)
-                }
-            },
-            false => match steam::get_steam_id_from_non_vanity_url(&normalized_url) {
-                Some(steam_id) => Ok(handle_new_player(&steam_id, &normalized_url).await),
-                None => {
-                    let msg = format!(
-                        "Could not resolve steam id from profile url: {}",
-                        normalized_url
-                    );
-                    tracking::track_error(&msg).await;
-                    Err(msg)
-                }
-            },
-        },
-    }
+async fn old_player_route(url: &str) -> Deprecated<Result<String, LookupError>> {
+    tracking::track_legacy_request(url).await;
+    Deprecated(lookup::lookup_player(url, MatchWindow::default()).await)
 }
 
-async fn handle_new_player(steam_id: &str, url: &str) -> String {
-    let faceit_data = faceit::get_player_details(steam_id).await;
-
-    // Only get last matches, total matches, and bans if we have valid faceit data
-    let (last_matches, bans) = if let Some(ref player_details) = faceit_data {
-        let last_matches = faceit::get_player_last_matches(&player_details.player_id).await;
-        let bans = faceit::get_player_bans(&player_details.player_id).await;
-        (last_matches, bans)
-    } else {
-        (None, None)
-    };
-
-    // Fetch CS2 hours, account creation date, and VAC ban info
-    let cs2_hours = steam::get_cs2_hours(steam_id).await;
-    let steam_account_created = steam::get_account_creation_date(steam_id).await;
-    let vac_ban_info = steam::get_vac_ban_info(steam_id).await;
-
-    let player = create_player(
-        steam_id,
-        faceit_data,
-        last_matches,
-        cs2_hours,
-        steam_account_created,
-        vac_ban_info,
-        bans,
-    );
-    match serde_json::to_string(&player) {
-        Ok(json) => {
-            redis::set(&url, &json);
-            redis::expire(&url, 60 * 60 * 24); // 1 day
-            json
-        }
-        Err(e) => {
-            let msg = format!("Error serializing player: {:?}", e);
-            tracking::track_error(&msg).await;
-            msg
-        }
-    }
+/// Converts any supported input into every representation of the Steam account
+#[get("/<input>")]
+async fn steam_id_route(input: &str) -> Result<Json<SteamIdentity>, LookupError> {
+    lookup::lookup_identity(input).await.map(Json)
 }
 
-fn create_player(
-    steam_id: &str,
-    faceit_data: Option<faceit::FaceitPlayerDetailsAPIResponse>,
-    last_matches: Option<faceit::PlayerLastMatchesResponse>,
-    cs2_hours: Option<u32>,
-    account_created: Option<i64>,
-    vac_ban_info: Option<VacBanInfo>,
-    bans: Option<Vec<faceit::FaceitBan>>,
-) -> Player {
-    let mut sites = vec![
-        Site {
-            url: format!("https://leetify.com/app/profile/{}", steam_id),
-            title: "Leetify".to_string(),
-        },
-        Site {
-            url: format!("https://csstats.gg/player/{}", steam_id),
-            title: "CsStats".to_string(),
-        },
-        Site {
-            url: format!("https://faceitfinder.com/profile/{}", steam_id),
-            title: "Faceitfinder".to_string(),
-        },
-        Site {
-            url: format!("https://steamcommunity.com/profiles/{}", steam_id),
-            title: "Steam".to_string(),
-        },
-    ];
-
-    if let Some(data) = &faceit_data {
-        sites.insert(
-            0,
-            Site {
-                url: data.faceit_url.replace("{lang}", "en"),
-                title: "Faceit".to_string(),
-            },
-        );
-    }
+/// Suggests Faceit players for the search box while typing
+#[get("/?<q>")]
+async fn search_route(q: &str) -> Result<RawJson<String>, LookupError> {
+    lookup::search(q).await.map(RawJson)
+}
 
-    Player {
-        steam_id: steam_id.to_string(),
-        faceit_data: faceit::from_api(faceit_data, last_matches, bans),
-        cs2_hours,
-        account_created,
-        vac_ban_info,
-        sites,
+/// Looks up every player of a lobby from the pasted output of the CS2 console `status` command
+#[post("/", data = "<status>")]
+async fn lobby_route(status: &str) -> Result<Json<Lobby>, LookupError> {
+    lobby::lookup_lobby(status).await.map(Json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::http::Status;
+    use rocket::local::asynchronous::Client;
+
+    #[rocket::async_test]
+    async fn legacy_player_route_announces_its_successor() {
+        // Tracking events go nowhere, the invalid input fails before any other service is asked
+        std::env::set_var("TRACKING_URL", "http://127.0.0.1:9/");
+        let rocket = rocket::build().mount("/player", routes![old_player_route]);
+        let client = Client::tracked(rocket).await.unwrap();
+
+        let response = client.get("/player/12345").dispatch().await;
+
+        assert_eq!(response.status(), Status::BadRequest);
+        let headers = response.headers();
+        assert_eq!(headers.get_one("Deprecation"), Some("@1792281600"));
+        assert_eq!(
+            headers.get_one("Sunset"),
+            Some("Sun, 18 Apr 2027 00:00:00 GMT")
+        );
+        assert_eq!(
+            headers.get_one("Link"),
+            Some("</api/v1/player/12345>; rel=\"successor-version\"")
+        );
     }
 }
@@ -10,30 +10,49 @@ fn create_pool() -> RedisPool {
     let manager = RedisConnectionManager::new(parse_redis_url(&redis_url).unwrap())
         .expect("Invalid connection URL");
 
-    Pool::builder()
-        .build(manager)
-        .expect("Failed to create pool")
+    // Connections are established lazily, so an unreachable Redis surfaces as an error
+    // on the first command instead of a panic when the pool is created
+    Pool::builder().build_unchecked(manager)
 }
 
 lazy_static::lazy_static! {
     static ref POOL: RedisPool = create_pool();
 }
 
-pub fn set(key: &str, value: &str) {
-    let pool = POOL.clone();
-    let mut con = pool.get().expect("Failed to get connection from pool");
-    let _: () = con.set(key, value).expect("failed to set key");
+pub fn set(key: &str, value: &str) -> Result<(), String> {
+    let mut con = POOL.get().map_err(|e| e.to_string())?;
+    con.set(key, value).map_err(|e| e.to_string())
 }
 
-pub fn get(key: &str) -> Option<String> {
-    let pool = POOL.clone();
-    let mut con = pool.get().expect("Failed to get connection from pool");
-    let value: Option<String> = con.get(key).expect("failed to get key");
-    value
+pub fn get(key: &str) -> Result<Option<String>, String> {
+    let mut con = POOL.get().map_err(|e| e.to_string())?;
+    con.get(key).map_err(|e| e.to_string())
 }
 
-pub fn expire(key: &str, seconds: usize) {
-    let pool = POOL.clone();
-    let mut con = pool.get().expect("Failed to get connection from pool");
-    let _: () = con.expire(key, seconds).expect("failed to set expiry");
+pub fn expire(key: &str, seconds: usize) -> Result<(), String> {
+    let mut con = POOL.get().map_err(|e| e.to_string())?;
+    con.expire(key, seconds).map_err(|e| e.to_string())
+}
+
+pub fn zadd(key: &str, member: &str, score: u64) -> Result<(), String> {
+    let mut con = POOL.get().map_err(|e| e.to_string())?;
+    con.zadd(key, member, score).map_err(|e| e.to_string())
+}
+
+/// The member of a sorted set with the highest score up to `max`
+pub fn zlast_until(key: &str, max: u64) -> Result<Option<String>, String> {
+    let mut con = POOL.get().map_err(|e| e.to_string())?;
+    let members: Vec<String> = con
+        .zrevrangebyscore_limit(key, max, "-inf", 0, 1)
+        .map_err(|e| e.to_string())?;
+    Ok(members.into_iter().next())
+}
+
+/// The member of a sorted set with the lowest score from `min` on
+pub fn zfirst_from(key: &str, min: u64) -> Result<Option<String>, String> {
+    let mut con = POOL.get().map_err(|e| e.to_string())?;
+    let members: Vec<String> = con
+        .zrangebyscore_limit(key, min, "+inf", 0, 1)
+        .map_err(|e| e.to_string())?;
+    Ok(members.into_iter().next())
 }
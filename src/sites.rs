@@ -0,0 +1,70 @@
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct Site {
+    pub url: String,
+    pub title: String,
+}
+
+/// A site that identifies a profile by its SteamID64 in the url path,
+/// e.g. https://leetify.com/app/profile/<steam_id>
+struct SteamIdSite {
+    title: &'static str,
+    host: &'static str,
+    profile_path: &'static str,
+}
+
+impl SteamIdSite {
+    fn profile_url(&self, steam_id: &str) -> String {
+        format!("https://{}/{}/{}", self.host, self.profile_path, steam_id)
+    }
+}
+
+/// Every site besides Faceit we link to. Profile urls of these sites are accepted as lookup input as well,
+/// see `input::ProfileInput`.
+static STEAM_ID_SITES: &[SteamIdSite] = &[
+    SteamIdSite {
+        title: "Leetify",
+        host: "leetify.com",
+        profile_path: "app/profile",
+    },
+    SteamIdSite {
+        title: "CsStats",
+        host: "csstats.gg",
+        profile_path: "player",
+    },
+    SteamIdSite {
+        title: "Faceitfinder",
+        host: "faceitfinder.com",
+        profile_path: "profile",
+    },
+    SteamIdSite {
+        title: "Steam",
+        host: "steamcommunity.com",
+        profile_path: "profiles",
+    },
+];
+
+/// Links to the profile of a player on every known site, Faceit first if the player has an account
+pub fn links(steam_id: &str, faceit_url: Option<&str>) -> Vec<Site> {
+    let faceit_site = faceit_url.map(|faceit_url| Site {
+        url: faceit_url.replace("{lang}", "en"),
+        title: "Faceit".to_string(),
+    });
+
+    faceit_site
+        .into_iter()
+        .chain(STEAM_ID_SITES.iter().map(|site| Site {
+            url: site.profile_url(steam_id),
+            title: site.title.to_string(),
+        }))
+        .collect()
+}
+
+/// The path in front of the SteamID64 in profile urls of the site with the given host
+pub fn profile_path(host: &str) -> Option<&'static str> {
+    STEAM_ID_SITES
+        .iter()
+        .find(|site| site.host == host)
+        .map(|site| site.profile_path)
+}
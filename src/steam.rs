@@ -1,167 +1,1129 @@
-use reqwest::Url;
-use serde::Serialize;
+use chrono::{Datelike, NaiveDateTime, Utc};
+use regex::Regex;
+use reqwest::header::LOCATION;
+use reqwest::redirect::Policy;
+use reqwest::{StatusCode, Url};
+use rocket::futures::future::join_all;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::cmp::Reverse;
+use std::collections::HashMap;
 
 use crate::env;
+use crate::error::LookupError;
+
+/// CS2's app ID
+const CS2_APP_ID: u32 = 730;
+
+/// SteamID64 of the individual account with account ID 0
+const STEAM_ID_64_BASE: u64 = 76561197960265728;
+
+/// Steam writes the hex digits of an account ID with these letters in friend invite codes
+const INVITE_CODE_ALPHABET: &str = "bcdfghjkmnpqrtvw";
+
+/// How many redirects of the short link host are followed before giving up
+const MAX_SHORT_LINK_REDIRECTS: usize = 5;
+
+lazy_static::lazy_static! {
+    static ref STEAM2_ID: Regex = Regex::new(r"^STEAM_[0-5]:([01]):(\d+)$").unwrap();
+    // Either with both brackets or with none, e.g. [U:1:24690] or U:1:24690
+    static ref STEAM3_ID: Regex = Regex::new(r"^(?:\[U:1:(\d+)\]|U:1:(\d+))$").unwrap();
+}
+
+/// Why a field of the player is there or not, so that a private profile can be told apart
+/// from a failing Steam API or a player that simply has nothing to show
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Availability {
+    Available,
+    /// Hidden by the privacy settings of the profile
+    Private,
+    /// Steam doesn't have it for this account
+    NotFound,
+    /// Steam couldn't be asked, it might be there next time
+    UpstreamError,
+}
+
+impl Availability {
+    /// Availability of a fetched field that Steam doesn't hide on private profiles
+    pub fn of<T>(result: &Result<Option<T>, LookupError>) -> Self {
+        match result {
+            Ok(Some(_)) => Availability::Available,
+            Ok(None) => Availability::NotFound,
+            Err(_) => Availability::UpstreamError,
+        }
+    }
+    /// Availability of a fetched field that Steam leaves out when it is hidden,
+    /// `hidden` tells why a missing field is missing
+    pub fn of_hideable<T>(result: &Result<Option<T>, LookupError>, hidden: Availability) -> Self {
+        match result {
+            Ok(Some(_)) => Availability::Available,
+            Ok(None) => hidden,
+            Err(_) => Availability::UpstreamError,
+        }
+    }
+}
 
 #[derive(Serialize)]
 pub struct VacBanInfo {
     is_banned: bool,
     ban_count: u32,
     days_since_last_ban: Option<u32>,
+    community_banned: bool,
+    /// "none", "probation" or "banned"
+    economy_ban: String,
+    game_ban_count: u32,
 }
 
-/// if a url is a vanity url
-/// vanity url is a url that looks like this: https://steamcommunity.com/id/username
-pub fn is_vanity_url(url: &str) -> bool {
-    let parsed_url = Url::parse(url).unwrap();
-    let segments: Vec<&str> = parsed_url.path_segments().unwrap().collect();
-    segments.len() >= 2 && segments[0] == "id"
+/// The public profile summary of a Steam account, as returned by GetPlayerSummaries
+#[derive(Deserialize, Serialize)]
+pub struct SteamProfile {
+    #[serde(rename(deserialize = "personaname"))]
+    persona_name: String,
+    #[serde(rename(deserialize = "profileurl"))]
+    profile_url: String,
+    avatar: String,
+    #[serde(rename(deserialize = "avatarmedium"))]
+    avatar_medium: String,
+    #[serde(rename(deserialize = "avatarfull"))]
+    avatar_full: String,
+    /// 1 if the profile is private or friends only, 3 if it is public
+    #[serde(rename(deserialize = "communityvisibilitystate"))]
+    community_visibility_state: u8,
+    /// 1 if the user has set up a community profile
+    #[serde(rename(deserialize = "profilestate"), default)]
+    profile_state: u8,
+    #[serde(rename(deserialize = "loccountrycode"))]
+    country_code: Option<String>,
+    #[serde(rename(deserialize = "lastlogoff"))]
+    last_logoff: Option<i64>,
+    #[serde(rename(deserialize = "realname"))]
+    real_name: Option<String>,
+    // Exposed as account_created of the player instead
+    #[serde(rename(deserialize = "timecreated"), skip_serializing)]
+    pub time_created: Option<i64>,
 }
 
-pub async fn get_steam_id_from_vanity_url(url: &str) -> Option<String> {
-    let username = get_username_from_vanity_url(url);
+impl SteamProfile {
+    pub fn is_public(&self) -> bool {
+        self.community_visibility_state == 3
+    }
 
-    if let Some(username) = username {
-        let api_url = format!(
-            "https://api.steampowered.com/ISteamUser/ResolveVanityURL/v1/?key={}&vanityurl={}",
-            env::get("STEAM_API_KEY"),
-            username
-        );
-        let response = reqwest::get(&api_url).await.ok()?;
+    /// The custom name of the vanity url, None if the profile has none
+    pub fn vanity_name(&self) -> Option<String> {
+        let parsed_url = Url::parse(&self.profile_url).ok()?;
+        let segments: Vec<&str> = parsed_url.path_segments()?.collect();
 
-        let json: serde_json::Value = response.json().await.ok()?;
-        if let Some(steam_id) = json["response"]["steamid"].as_str() {
-            return Some(steam_id.to_string());
+        match segments.as_slice() {
+            ["id", name, ..] => Some(name.to_string()),
+            _ => None,
         }
     }
+}
+
+/// Lifetime CS2 stats of the in-game stats tracking, as returned by GetUserStatsForGame
+#[derive(Serialize)]
+pub struct SteamGameStats {
+    kills: u64,
+    deaths: u64,
+    kd_ratio: f32,
+    headshot_percentage: f32,
+    /// Share of fired shots that hit, in percent
+    accuracy: f32,
+    mvps: u64,
+    /// Rounds won
+    wins: u64,
+    rounds_played: u64,
+    matches_won: u64,
+    matches_played: u64,
+    /// Ordered by rounds played
+    maps: Vec<SteamMapStats>,
+    /// Ordered by kills
+    weapons: Vec<SteamWeaponStats>,
+}
 
-    None
+#[derive(Serialize)]
+pub struct SteamMapStats {
+    map: String,
+    rounds: u64,
+    wins: u64,
 }
 
-pub fn get_steam_id_from_non_vanity_url(url: &str) -> Option<String> {
-    let parsed_url = Url::parse(url).unwrap();
-    let segments: Vec<&str> = parsed_url.path_segments().unwrap().collect();
+#[derive(Serialize)]
+pub struct SteamWeaponStats {
+    weapon: String,
+    kills: u64,
+    shots: u64,
+    hits: u64,
+    /// Share of fired shots that hit, in percent
+    accuracy: f32,
+}
 
-    if segments.len() < 2 || segments[0] != "profiles" {
-        None
+/// Weapons without shot stats, they only have kills
+const WEAPONS_WITHOUT_SHOTS: &[&str] = &["knife", "hegrenade", "molotov"];
+
+/// How many friends of a Steam account are banned
+#[derive(Serialize)]
+pub struct FriendsAnalysis {
+    friend_count: u32,
+    vac_banned_count: u32,
+    game_banned_count: u32,
+    /// Share of friends with a VAC or game ban, in percent
+    banned_percentage: f32,
+    /// Most recently banned first
+    banned_friends: Vec<BannedFriend>,
+}
+
+#[derive(Serialize)]
+pub struct BannedFriend {
+    steam_id: String,
+    vac_ban_count: u32,
+    game_ban_count: u32,
+    days_since_last_ban: Option<u32>,
+    /// Unix timestamp of when the friendship started
+    friend_since: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct SteamBadges {
+    badge_count: u32,
+    xp: u32,
+    /// Level of the years of service badge, i.e. how many years the account exists
+    years_of_service: Option<u32>,
+}
+
+/// Badge ID of the years of service badge
+const YEARS_OF_SERVICE_BADGE_ID: u64 = 1;
+
+/// Summary of the game library, an account owning nothing but CS2 is a common smurf pattern
+#[derive(Serialize)]
+pub struct LibrarySummary {
+    game_count: u32,
+    total_hours: u32,
+    /// The games with the most playtime, at most `TOP_GAMES_COUNT`
+    top_games: Vec<TopGame>,
+}
+
+#[derive(Serialize)]
+pub struct TopGame {
+    appid: u32,
+    name: Option<String>,
+    hours: u32,
+}
+
+/// How many games the library summary lists by playtime
+const TOP_GAMES_COUNT: usize = 5;
+
+/// A former persona name of a Steam account
+#[derive(Deserialize, Serialize)]
+pub struct NameHistoryEntry {
+    #[serde(rename(deserialize = "newname"))]
+    name: String,
+    /// Unix timestamp of the rename, None if Steam's date couldn't be parsed
+    #[serde(
+        rename(deserialize = "timechanged"),
+        deserialize_with = "parse_alias_timestamp"
+    )]
+    changed_at: Option<i64>,
+}
+
+/// Every representation of a Steam account
+#[derive(Serialize)]
+pub struct SteamIdentity {
+    steam_id_64: String,
+    steam2: String,
+    steam3: String,
+    account_id: u32,
+    /// The code of the friend invite link, e.g. `dbn-npdf`
+    friend_code: String,
+    friend_invite_url: String,
+    profile_url: String,
+    vanity_name: Option<String>,
+}
+
+/// Sends a request to the Steam Web API, failing on error statuses and unreadable responses
+async fn steam_api_request(api_url: &str) -> Result<serde_json::Value, LookupError> {
+    let response = reqwest::get(api_url)
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| LookupError::SteamApi(e.to_string()))?;
+
+    response
+        .json()
+        .await
+        .map_err(|e| LookupError::SteamApi(e.to_string()))
+}
+
+/// Like `steam_api_request`, but a response with one of the `hidden` statuses means Steam won't
+/// show the data, e.g. because of the privacy settings, and is None instead of an error
+async fn steam_api_request_unless(
+    api_url: &str,
+    hidden: &[StatusCode],
+) -> Result<Option<serde_json::Value>, LookupError> {
+    let response = reqwest::get(api_url)
+        .await
+        .map_err(|e| LookupError::SteamApi(e.to_string()))?;
+    if hidden.contains(&response.status()) {
+        return Ok(None);
+    }
+
+    response
+        .error_for_status()
+        .map_err(|e| LookupError::SteamApi(e.to_string()))?
+        .json()
+        .await
+        .map(Some)
+        .map_err(|e| LookupError::SteamApi(e.to_string()))
+}
+
+/// Resolves the custom name of a vanity url (https://steamcommunity.com/id/<name>) to a SteamID64
+pub async fn get_steam_id_from_vanity_name(name: &str) -> Result<String, LookupError> {
+    let api_url = format!(
+        "https://api.steampowered.com/ISteamUser/ResolveVanityURL/v1/?key={}&vanityurl={}",
+        env::get("STEAM_API_KEY"),
+        name
+    );
+    let response = reqwest::get(&api_url)
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| LookupError::SteamApi(e.to_string()))?;
+
+    let json: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| LookupError::SteamApi(e.to_string()))?;
+    json["response"]["steamid"]
+        .as_str()
+        .map(|steam_id| steam_id.to_string())
+        .ok_or_else(|| LookupError::VanityNotResolvable(name.to_string()))
+}
+
+/// Parses a bare Steam ID in one of the common formats:
+/// SteamID64 (76561197960290418), Steam2 (STEAM_1:0:12345) or Steam3 ([U:1:24690])
+/// Returns the SteamID64, or None if the input is not a Steam ID
+pub fn parse_steam_id(input: &str) -> Option<String> {
+    let input = input.trim();
+
+    let account_id = if let Some(captures) = STEAM2_ID.captures(input) {
+        let y = captures[1].parse::<u64>().ok()?;
+        let z = captures[2].parse::<u64>().ok()?;
+        z.checked_mul(2)?.checked_add(y)?
+    } else if let Some(captures) = STEAM3_ID.captures(input) {
+        captures
+            .get(1)
+            .or_else(|| captures.get(2))?
+            .as_str()
+            .parse::<u64>()
+            .ok()?
+    } else if input.len() == 17 && input.chars().all(|c| c.is_ascii_digit()) {
+        input.parse::<u64>().ok()?.checked_sub(STEAM_ID_64_BASE)?
     } else {
-        Some(segments[1].to_string())
+        return None;
+    };
+
+    if account_id > u32::MAX as u64 {
+        return None;
     }
+
+    Some((STEAM_ID_64_BASE + account_id).to_string())
 }
 
-fn get_username_from_vanity_url(url: &str) -> Option<String> {
-    let parsed_url = Url::parse(url).unwrap();
-    let segments: Vec<&str> = parsed_url.path_segments().unwrap().collect();
+/// Decodes a friend invite code like `dbnn-pdf` into a SteamID64
+/// These codes are used by s.team/p/<code> and steamcommunity.com/user/<code> links
+pub fn steam_id_from_invite_code(code: &str) -> Option<String> {
+    let hex = code
+        .to_lowercase()
+        .chars()
+        .filter(|c| *c != '-')
+        .map(|c| {
+            INVITE_CODE_ALPHABET
+                .find(c)
+                .and_then(|digit| char::from_digit(digit as u32, 16))
+        })
+        .collect::<Option<String>>()?;
 
-    if segments.len() < 2 || segments[0] != "id" {
-        None
+    let account_id = u32::from_str_radix(&hex, 16).ok()?;
+    Some((STEAM_ID_64_BASE + account_id as u64).to_string())
+}
+
+/// Encodes the account ID of a SteamID64 as friend invite code, the reverse of `steam_id_from_invite_code`
+fn invite_code(account_id: u32) -> String {
+    let code: String = format!("{:x}", account_id)
+        .chars()
+        .filter_map(|digit| {
+            let index = digit.to_digit(16)? as usize;
+            INVITE_CODE_ALPHABET.chars().nth(index)
+        })
+        .collect();
+
+    if code.len() > 3 {
+        let (first, second) = code.split_at(code.len() / 2);
+        format!("{}-{}", first, second)
     } else {
-        Some(segments[1].to_string())
+        code
     }
 }
 
-pub fn normalize_url(url: &str) -> Result<String, String> {
-    let mut parsed_url = Url::parse(url).unwrap();
-    parsed_url.set_query(None);
-    parsed_url.set_fragment(None);
+/// The account ID of a SteamID64, None if it's not the SteamID64 of an individual account
+pub fn account_id(steam_id: &str) -> Option<u32> {
+    let account_id = steam_id
+        .parse::<u64>()
+        .ok()?
+        .checked_sub(STEAM_ID_64_BASE)?;
+    u32::try_from(account_id).ok()
+}
+
+/// All representations of the account with the given SteamID64
+pub fn identity(steam_id: &str, vanity_name: Option<String>) -> Option<SteamIdentity> {
+    let account_id = account_id(steam_id)?;
+    let friend_code = invite_code(account_id);
+
+    Some(SteamIdentity {
+        steam_id_64: steam_id.to_string(),
+        steam2: format!("STEAM_1:{}:{}", account_id % 2, account_id / 2),
+        steam3: format!("[U:1:{}]", account_id),
+        account_id,
+        friend_invite_url: format!("https://s.team/p/{}", friend_code),
+        friend_code,
+        profile_url: profile_url(steam_id),
+        vanity_name,
+    })
+}
+
+/// Resolves a Steam short link to the url it points to by following its redirects
+/// on the short link host until they leave it.
+/// The host can be overridden with STEAM_SHORT_LINK_URL.
+pub async fn resolve_short_link(url: &Url) -> Result<String, LookupError> {
+    let not_found = || LookupError::PlayerNotFound(url.to_string());
+    let client = reqwest::Client::builder()
+        .redirect(Policy::none())
+        .build()
+        .map_err(|e| LookupError::SteamApi(e.to_string()))?;
+    let mut current_url = Url::parse(&env::get_or("STEAM_SHORT_LINK_URL", "https://s.team"))
+        .and_then(|short_link_host| short_link_host.join(url.path()))
+        .map_err(|e| LookupError::SteamApi(e.to_string()))?;
+
+    for _ in 0..MAX_SHORT_LINK_REDIRECTS {
+        let response = client
+            .get(current_url.clone())
+            .send()
+            .await
+            .map_err(|e| LookupError::SteamApi(e.to_string()))?;
+        if response.status().is_server_error() {
+            return Err(LookupError::SteamApi(format!(
+                "Short link host responded with {}",
+                response.status()
+            )));
+        }
+        if !response.status().is_redirection() {
+            return Err(not_found());
+        }
 
-    let segments: Vec<&str> = parsed_url.path_segments().unwrap().collect();
-    if segments.len() >= 2 && (segments[0] == "id" || segments[0] == "profiles") {
-        let base_path = format!("{}/{}", segments[0], segments[1]);
-        parsed_url.set_path(&base_path);
-        return Ok(parsed_url.to_string());
+        let location = response
+            .headers()
+            .get(LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .ok_or_else(not_found)?;
+        let next_url = current_url.join(location).map_err(|_| not_found())?;
+        if next_url.host_str() != current_url.host_str() {
+            return Ok(next_url.to_string());
+        }
+        current_url = next_url;
     }
 
-    Err("Invalid URL format".to_string())
+    Err(not_found())
+}
+
+/// The canonical steamcommunity.com profile url for a SteamID64
+pub fn profile_url(steam_id: &str) -> String {
+    format!("https://steamcommunity.com/profiles/{}", steam_id)
+}
+
+/// A game of the library as returned by GetOwnedGames, playtimes are in minutes
+#[derive(Deserialize)]
+pub struct OwnedGame {
+    pub appid: u32,
+    pub name: Option<String>,
+    #[serde(default)]
+    pub playtime_forever: u32,
+    #[serde(default)]
+    pub playtime_2weeks: u32,
 }
 
-/// Fetch CS2 hours for a given Steam ID
-/// Returns the number of hours played in CS2, or None if not found/error
-pub async fn get_cs2_hours(steam_id: &str) -> Option<u32> {
+/// Fetch the game library for a given Steam ID
+/// Returns None if the library is private
+pub async fn get_owned_games(steam_id: &str) -> Result<Option<Vec<OwnedGame>>, LookupError> {
     let api_url = format!(
         "https://api.steampowered.com/IPlayerService/GetOwnedGames/v1/?key={}&steamid={}&include_appinfo=1&include_played_free_games=1",
         env::get("STEAM_API_KEY"),
         steam_id
     );
 
-    let response = reqwest::get(&api_url).await.ok()?;
-    let json: serde_json::Value = response.json().await.ok()?;
-
-    // CS2's app ID is 730
-    if let Some(games) = json["response"]["games"].as_array() {
-        for game in games {
-            if let Some(app_id) = game["appid"].as_u64() {
-                if app_id == 730 {
-                    // Convert minutes to hours (playtime_forever is in minutes)
-                    if let Some(minutes) = game["playtime_forever"].as_u64() {
-                        return Some((minutes / 60) as u32);
-                    }
-                }
-            }
-        }
+    let json = steam_api_request(&api_url).await?;
+
+    // Steam leaves out the games of a private library instead of failing
+    if json["response"]["games"].is_null() {
+        return Ok(None);
     }
+    serde_json::from_value(json["response"]["games"].clone())
+        .map(Some)
+        .map_err(|e| LookupError::SteamApi(e.to_string()))
+}
+
+/// Summarizes the game library, see `LibrarySummary`
+/// Free-to-play games are only listed once played, GetOwnedGames doesn't tell them apart
+/// from paid ones, so the summary doesn't either
+pub fn get_library_summary(games: &[OwnedGame]) -> LibrarySummary {
+    let total_minutes: u64 = games.iter().map(|game| game.playtime_forever as u64).sum();
+
+    let mut top_games: Vec<&OwnedGame> = games.iter().collect();
+    top_games.sort_by_key(|game| Reverse(game.playtime_forever));
+
+    LibrarySummary {
+        game_count: games.len() as u32,
+        total_hours: (total_minutes / 60) as u32,
+        top_games: top_games
+            .into_iter()
+            .take(TOP_GAMES_COUNT)
+            .map(|game| TopGame {
+                appid: game.appid,
+                name: game.name.clone(),
+                hours: game.playtime_forever / 60,
+            })
+            .collect(),
+    }
+}
+
+/// CS2 hours from the game library
+/// Returns the number of hours played in CS2, or None if CS2 is not in the library
+pub fn get_cs2_hours(games: &[OwnedGame]) -> Option<u32> {
+    // Convert minutes to hours (playtime_forever is in minutes)
+    cs2(games).map(|game| game.playtime_forever / 60)
+}
+
+/// CS2 hours of the last two weeks from the game library
+/// Returns None if CS2 is not in the library
+pub fn get_cs2_hours_2weeks(games: &[OwnedGame]) -> Option<u32> {
+    cs2(games).map(|game| game.playtime_2weeks / 60)
+}
 
-    None
+fn cs2(games: &[OwnedGame]) -> Option<&OwnedGame> {
+    games.iter().find(|game| game.appid == CS2_APP_ID)
 }
 
-/// Fetch account creation date for a given Steam ID
-/// Returns the Unix timestamp when the account was created, or None if not found/error
-pub async fn get_account_creation_date(steam_id: &str) -> Option<i64> {
+/// Fetch recent CS2 hours for a given Steam ID from GetRecentlyPlayedGames
+/// Returns the hours played in the last two weeks, or None if the game details are private
+pub async fn get_cs2_recent_hours(steam_id: &str) -> Result<Option<u32>, LookupError> {
     let api_url = format!(
-        "https://api.steampowered.com/ISteamUser/GetPlayerSummaries/v2/?key={}&steamids={}",
+        "https://api.steampowered.com/IPlayerService/GetRecentlyPlayedGames/v1/?key={}&steamid={}",
         env::get("STEAM_API_KEY"),
         steam_id
     );
 
-    let response = reqwest::get(&api_url).await.ok()?;
-    let json: serde_json::Value = response.json().await.ok()?;
+    let json = steam_api_request(&api_url).await?;
+
+    // Private game details come without total_count, no recently played games without games
+    if json["response"]["total_count"].as_u64().is_none() {
+        return Ok(None);
+    }
+    let games: Vec<OwnedGame> =
+        serde_json::from_value(json["response"]["games"].clone()).unwrap_or_default();
+
+    Ok(Some(
+        cs2(&games).map_or(0, |game| game.playtime_2weeks / 60),
+    ))
+}
+
+/// Fetch lifetime CS2 stats for a given Steam ID
+/// Returns None if the profile or its game details are private, or if there are no CS2 stats
+pub async fn get_game_stats(steam_id: &str) -> Result<Option<SteamGameStats>, LookupError> {
+    let api_url = format!(
+        "https://api.steampowered.com/ISteamUserStats/GetUserStatsForGame/v2/?key={}&steamid={}&appid={}",
+        env::get("STEAM_API_KEY"),
+        steam_id,
+        CS2_APP_ID
+    );
+
+    // Steam answers hidden game details with 403 and accounts without CS2 stats with 400
+    let hidden = [StatusCode::BAD_REQUEST, StatusCode::FORBIDDEN];
+    let Some(json) = steam_api_request_unless(&api_url, &hidden).await? else {
+        return Ok(None);
+    };
+    let Some(stats) = json["playerstats"]["stats"].as_array() else {
+        return Ok(None);
+    };
+
+    let stats: HashMap<&str, u64> = stats
+        .iter()
+        .filter_map(|stat| Some((stat["name"].as_str()?, stat["value"].as_u64()?)))
+        .collect();
+    let stat = |name: &str| stats.get(name).copied().unwrap_or(0);
+
+    let mut maps: Vec<SteamMapStats> = stats
+        .keys()
+        .filter_map(|name| name.strip_prefix("total_rounds_map_"))
+        .map(|map| SteamMapStats {
+            map: map.to_string(),
+            rounds: stat(&format!("total_rounds_map_{}", map)),
+            wins: stat(&format!("total_wins_map_{}", map)),
+        })
+        .collect();
+    maps.sort_by_key(|map| Reverse(map.rounds));
 
-    if let Some(players) = json["response"]["players"].as_array() {
-        if let Some(player) = players.first() {
-            if let Some(timecreated) = player["timecreated"].as_i64() {
-                return Some(timecreated);
+    let mut weapons: Vec<SteamWeaponStats> = stats
+        .keys()
+        .filter_map(|name| name.strip_prefix("total_shots_"))
+        .filter(|weapon| *weapon != "fired" && *weapon != "hit")
+        .chain(WEAPONS_WITHOUT_SHOTS.iter().copied())
+        .map(|weapon| {
+            let shots = stat(&format!("total_shots_{}", weapon));
+            let hits = stat(&format!("total_hits_{}", weapon));
+
+            SteamWeaponStats {
+                weapon: weapon.to_string(),
+                kills: stat(&format!("total_kills_{}", weapon)),
+                shots,
+                hits,
+                accuracy: percentage(hits, shots),
             }
-        }
+        })
+        .collect();
+    weapons.sort_by_key(|weapon| Reverse(weapon.kills));
+
+    let kills = stat("total_kills");
+    let deaths = stat("total_deaths");
+
+    Ok(Some(SteamGameStats {
+        kills,
+        deaths,
+        kd_ratio: if deaths > 0 {
+            kills as f32 / deaths as f32
+        } else {
+            0.0
+        },
+        headshot_percentage: percentage(stat("total_kills_headshot"), kills),
+        accuracy: percentage(stat("total_shots_hit"), stat("total_shots_fired")),
+        mvps: stat("total_mvps"),
+        wins: stat("total_wins"),
+        rounds_played: stat("total_rounds_played"),
+        matches_won: stat("total_matches_won"),
+        matches_played: stat("total_matches_played"),
+        maps,
+        weapons,
+    }))
+}
+
+fn percentage(part: u64, total: u64) -> f32 {
+    if total > 0 {
+        (part as f32 / total as f32) * 100.0
+    } else {
+        0.0
     }
+}
 
-    None
+/// Fetch the persona name history for a given Steam ID from the public alias list of the profile
+/// The community host can be overridden with STEAM_COMMUNITY_URL.
+/// Returns the former names, most recent first
+pub async fn get_name_history(
+    steam_id: &str,
+) -> Result<Option<Vec<NameHistoryEntry>>, LookupError> {
+    let api_url = format!(
+        "{}/profiles/{}/ajaxaliases",
+        env::get_or("STEAM_COMMUNITY_URL", "https://steamcommunity.com"),
+        steam_id
+    );
+
+    let json = steam_api_request(&api_url).await?;
+
+    serde_json::from_value(json)
+        .map(Some)
+        .map_err(|e| LookupError::SteamApi(e.to_string()))
 }
 
-/// Fetch VAC ban information for a given Steam ID
-/// Returns a tuple of (VAC banned, number of VAC bans, days since last ban)
-pub async fn get_vac_ban_info(steam_id: &str) -> Option<VacBanInfo> {
+/// Fetch the Steam level for a given Steam ID
+/// Returns None if the profile is private
+pub async fn get_steam_level(steam_id: &str) -> Result<Option<u32>, LookupError> {
+    let api_url = format!(
+        "https://api.steampowered.com/IPlayerService/GetSteamLevel/v1/?key={}&steamid={}",
+        env::get("STEAM_API_KEY"),
+        steam_id
+    );
+
+    let json = steam_api_request(&api_url).await?;
+
+    Ok(json["response"]["player_level"]
+        .as_u64()
+        .map(|level| level as u32))
+}
+
+/// Fetch badge count, XP and the years of service badge for a given Steam ID
+/// Returns None if the badges are private
+pub async fn get_badges(steam_id: &str) -> Result<Option<SteamBadges>, LookupError> {
+    let api_url = format!(
+        "https://api.steampowered.com/IPlayerService/GetBadges/v1/?key={}&steamid={}",
+        env::get("STEAM_API_KEY"),
+        steam_id
+    );
+
+    let json = steam_api_request(&api_url).await?;
+
+    let Some(badges) = json["response"]["badges"].as_array() else {
+        return Ok(None);
+    };
+    // Game badges share badge IDs, only the community badges come without an app ID
+    let years_of_service = badges
+        .iter()
+        .find(|badge| {
+            badge["badgeid"].as_u64() == Some(YEARS_OF_SERVICE_BADGE_ID) && badge["appid"].is_null()
+        })
+        .and_then(|badge| badge["level"].as_u64())
+        .map(|level| level as u32);
+
+    Ok(Some(SteamBadges {
+        badge_count: badges.len() as u32,
+        xp: json["response"]["player_xp"].as_u64().unwrap_or(0) as u32,
+        years_of_service,
+    }))
+}
+
+/// Fetch the profile summary for a given Steam ID
+/// Returns None if there is no such account
+pub async fn get_player_summary(steam_id: &str) -> Result<Option<SteamProfile>, LookupError> {
+    let api_url = format!(
+        "https://api.steampowered.com/ISteamUser/GetPlayerSummaries/v2/?key={}&steamids={}",
+        env::get("STEAM_API_KEY"),
+        steam_id
+    );
+
+    let json = steam_api_request(&api_url).await?;
+
+    match json["response"]["players"]
+        .as_array()
+        .and_then(|players| players.first())
+    {
+        Some(player) => serde_json::from_value(player.clone())
+            .map(Some)
+            .map_err(|e| LookupError::SteamApi(e.to_string())),
+        None => Ok(None),
+    }
+}
+
+/// GetPlayerBans accepts at most this many Steam IDs per request
+const PLAYER_BANS_BATCH_SIZE: usize = 100;
+
+/// Fetch the raw GetPlayerBans entries for up to `PLAYER_BANS_BATCH_SIZE` Steam IDs
+async fn get_player_bans(steam_ids: &[&str]) -> Result<Vec<serde_json::Value>, LookupError> {
     let api_url = format!(
         "https://api.steampowered.com/ISteamUser/GetPlayerBans/v1/?key={}&steamids={}",
         env::get("STEAM_API_KEY"),
+        steam_ids.join(",")
+    );
+
+    let json = steam_api_request(&api_url).await?;
+
+    json["players"]
+        .as_array()
+        .cloned()
+        .ok_or_else(|| LookupError::SteamApi("GetPlayerBans response without players".to_string()))
+}
+
+/// Fetch ban information for a given Steam ID
+/// Returns VAC, game, community and economy (trade) bans, None if there is no such account
+pub async fn get_vac_ban_info(steam_id: &str) -> Result<Option<VacBanInfo>, LookupError> {
+    let players = get_player_bans(&[steam_id]).await?;
+    Ok(players.first().map(vac_ban_info))
+}
+
+fn vac_ban_info(player: &serde_json::Value) -> VacBanInfo {
+    let vac_banned = player["VACBanned"].as_bool().unwrap_or(false);
+    let number_of_vac_bans = player["NumberOfVACBans"].as_u64().unwrap_or(0) as u32;
+    let community_banned = player["CommunityBanned"].as_bool().unwrap_or(false);
+    let economy_ban = player["EconomyBan"].as_str().unwrap_or("none").to_string();
+    let number_of_game_bans = player["NumberOfGameBans"].as_u64().unwrap_or(0) as u32;
+
+    // DaysSinceLastBan is -1 if no bans, otherwise it's the number of days
+    let days_since_last_ban = if let Some(days) = player["DaysSinceLastBan"].as_i64() {
+        if days >= 0 {
+            Some(days as u32)
+        } else {
+            None
+        }
+    } else {
+        None
+    };
+
+    VacBanInfo {
+        is_banned: vac_banned,
+        ban_count: number_of_vac_bans,
+        days_since_last_ban,
+        community_banned,
+        economy_ban,
+        game_ban_count: number_of_game_bans,
+    }
+}
+
+/// Fetch the friend list for a given Steam ID and check every friend for VAC and game bans
+/// Returns None if the friend list is private
+pub async fn get_friends_analysis(steam_id: &str) -> Result<Option<FriendsAnalysis>, LookupError> {
+    let api_url = format!(
+        "https://api.steampowered.com/ISteamUser/GetFriendList/v1/?key={}&steamid={}&relationship=friend",
+        env::get("STEAM_API_KEY"),
         steam_id
     );
 
-    let response = reqwest::get(&api_url).await.ok()?;
-    let json: serde_json::Value = response.json().await.ok()?;
-
-    if let Some(players) = json["players"].as_array() {
-        if let Some(player) = players.first() {
-            let vac_banned = player["VACBanned"].as_bool().unwrap_or(false);
-            let number_of_vac_bans = player["NumberOfVACBans"].as_u64().unwrap_or(0) as u32;
-
-            // DaysSinceLastBan is -1 if no bans, otherwise it's the number of days
-            let days_since_last_ban = if let Some(days) = player["DaysSinceLastBan"].as_i64() {
-                if days >= 0 {
-                    Some(days as u32)
-                } else {
-                    None
-                }
-            } else {
-                None
-            };
-
-            return Some(VacBanInfo {
-                is_banned: vac_banned,
-                ban_count: number_of_vac_bans,
-                days_since_last_ban,
+    // Steam answers a private friend list with 401
+    let Some(json) = steam_api_request_unless(&api_url, &[StatusCode::UNAUTHORIZED]).await? else {
+        return Ok(None);
+    };
+    let Some(friends) = json["friendslist"]["friends"].as_array() else {
+        return Ok(None);
+    };
+
+    let friends: HashMap<&str, Option<i64>> = friends
+        .iter()
+        .filter_map(|friend| Some((friend["steamid"].as_str()?, friend["friend_since"].as_i64())))
+        .collect();
+    let friend_ids: Vec<&str> = friends.keys().copied().collect();
+
+    let batches = join_all(
+        friend_ids
+            .chunks(PLAYER_BANS_BATCH_SIZE)
+            .map(get_player_bans),
+    )
+    .await;
+
+    let mut vac_banned_count = 0;
+    let mut game_banned_count = 0;
+    let mut banned_friends = Vec::new();
+
+    // A missing batch would understate the banned share, so fail as a whole
+    for player in batches
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?
+        .iter()
+        .flatten()
+    {
+        let bans = vac_ban_info(player);
+        if !bans.is_banned && bans.game_ban_count == 0 {
+            continue;
+        }
+
+        if bans.is_banned {
+            vac_banned_count += 1;
+        }
+        if bans.game_ban_count > 0 {
+            game_banned_count += 1;
+        }
+
+        let friend_id = player["SteamId"].as_str().unwrap_or_default();
+        banned_friends.push(BannedFriend {
+            steam_id: friend_id.to_string(),
+            vac_ban_count: bans.ban_count,
+            game_ban_count: bans.game_ban_count,
+            days_since_last_ban: bans.days_since_last_ban,
+            friend_since: friends.get(friend_id).copied().flatten(),
+        });
+    }
+    banned_friends.sort_by_key(|friend| {
+        (
+            friend.days_since_last_ban.is_none(),
+            friend.days_since_last_ban,
+        )
+    });
+
+    let friend_count = friends.len() as u32;
+    Ok(Some(FriendsAnalysis {
+        friend_count,
+        vac_banned_count,
+        game_banned_count,
+        banned_percentage: percentage(banned_friends.len() as u64, friend_count as u64),
+        banned_friends,
+    }))
+}
+
+/// Steam formats alias timestamps like `18 Oct, 2023 @ 3:12pm` and leaves out
+/// the year for renames of the current year, e.g. `18 Oct @ 3:12pm`
+fn parse_alias_timestamp<'de, D>(deserializer: D) -> Result<Option<i64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let time_changed: String = Deserialize::deserialize(deserializer)?;
+    let with_year = match time_changed.contains(',') {
+        true => time_changed,
+        false => time_changed.replacen(" @", &format!(", {} @", Utc::now().year()), 1),
+    };
+
+    Ok(
+        NaiveDateTime::parse_from_str(&with_year, "%d %b, %Y @ %I:%M%P")
+            .ok()
+            .map(|date| date.and_utc().timestamp()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stub_server::{response, stub_server};
+    use std::sync::OnceLock;
+
+    /// Stands in for s.team, shared by all tests as STEAM_SHORT_LINK_URL is process wide
+    fn short_link_stub() {
+        static STUB: OnceLock<String> = OnceLock::new();
+        STUB.get_or_init(|| {
+            let url = stub_server(|path| match path {
+                "/p/off-host" => response(
+                    "302 Found",
+                    "Location: https://steamcommunity.com/user/bcdf-ghjk\r\n",
+                    "",
+                ),
+                "/p/same-host" => response("302 Found", "Location: /p/off-host\r\n", ""),
+                "/p/loop" => response("302 Found", "Location: /p/loop\r\n", ""),
+                "/p/error" => response("503 Service Unavailable", "", ""),
+                _ => response("200 OK", "", "<html></html>"),
             });
+            std::env::set_var("STEAM_SHORT_LINK_URL", &url);
+            url
+        });
+    }
+
+    async fn resolve(path: &str) -> Result<String, LookupError> {
+        short_link_stub();
+        resolve_short_link(&Url::parse(&format!("https://s.team{}", path)).unwrap()).await
+    }
+
+    /// Stands in for steamcommunity.com, shared by all tests as STEAM_COMMUNITY_URL is process wide
+    fn community_stub() {
+        static STUB: OnceLock<String> = OnceLock::new();
+        STUB.get_or_init(|| {
+            let url = stub_server(|path| match path {
+                "/profiles/76561197960290418/ajaxaliases" => response(
+                    "200 OK",
+                    "Content-Type: application/json\r\n",
+                    r#"[{"newname":"second","timechanged":"8 Oct, 2023 @ 3:12pm"},{"newname":"first","timechanged":"sometime"}]"#,
+                ),
+                _ => response("500 Internal Server Error", "", ""),
+            });
+            std::env::set_var("STEAM_COMMUNITY_URL", &url);
+            url
+        });
+    }
+
+    fn alias_timestamp(time_changed: &str) -> Option<i64> {
+        let json = serde_json::json!({ "newname": "name", "timechanged": time_changed });
+        serde_json::from_value::<NameHistoryEntry>(json)
+            .unwrap()
+            .changed_at
+    }
+
+    fn timestamp(date_time: &str) -> i64 {
+        NaiveDateTime::parse_from_str(date_time, "%Y-%m-%d %H:%M")
+            .unwrap()
+            .and_utc()
+            .timestamp()
+    }
+
+    #[test]
+    fn parse_alias_timestamp_with_year() {
+        assert_eq!(
+            alias_timestamp("18 Oct, 2023 @ 3:12pm"),
+            Some(timestamp("2023-10-18 15:12"))
+        );
+        assert_eq!(
+            alias_timestamp("1 Jan, 2020 @ 12:05am"),
+            Some(timestamp("2020-01-01 00:05"))
+        );
+    }
+
+    #[test]
+    fn parse_alias_timestamp_without_year_is_this_year() {
+        let year = Utc::now().year();
+        assert_eq!(
+            alias_timestamp("18 Oct @ 3:12pm"),
+            Some(timestamp(&format!("{}-10-18 15:12", year)))
+        );
+        assert_eq!(
+            alias_timestamp("8 Feb @ 11:59am"),
+            Some(timestamp(&format!("{}-02-08 11:59", year)))
+        );
+    }
+
+    #[test]
+    fn parse_alias_timestamp_unparseable_is_none() {
+        assert_eq!(alias_timestamp(""), None);
+        assert_eq!(alias_timestamp("yesterday"), None);
+        assert_eq!(alias_timestamp("32 Oct, 2023 @ 3:12pm"), None);
+    }
+
+    #[rocket::async_test]
+    async fn get_name_history_from_fixture() {
+        community_stub();
+
+        let history = get_name_history("76561197960290418")
+            .await
+            .ok()
+            .flatten()
+            .unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].name, "second");
+        assert_eq!(history[0].changed_at, Some(timestamp("2023-10-08 15:12")));
+        assert_eq!(history[1].name, "first");
+        assert_eq!(history[1].changed_at, None);
+    }
+
+    #[rocket::async_test]
+    async fn get_name_history_upstream_error() {
+        community_stub();
+
+        assert!(matches!(
+            get_name_history("76561197960290419").await,
+            Err(LookupError::SteamApi(_))
+        ));
+    }
+
+    fn owned_games(playtimes: &[u32]) -> Vec<OwnedGame> {
+        playtimes
+            .iter()
+            .enumerate()
+            .map(|(index, playtime)| OwnedGame {
+                appid: index as u32,
+                name: None,
+                playtime_forever: *playtime,
+                playtime_2weeks: 0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn library_summary_counts_games_and_hours() {
+        let games = owned_games(&[600, 60, 6000, 0, 120, 30]);
+        let summary = get_library_summary(&games);
+
+        assert_eq!(summary.game_count, 6);
+        assert_eq!(summary.total_hours, 113);
+        let top: Vec<u32> = summary.top_games.iter().map(|game| game.appid).collect();
+        assert_eq!(top, vec![2, 0, 4, 1, 5]);
+    }
+
+    #[test]
+    fn invite_code_round_trips() {
+        for account_id in [1, 15, 16, 24690, 0x12345678, u32::MAX] {
+            assert_eq!(
+                steam_id_from_invite_code(&invite_code(account_id)),
+                Some((STEAM_ID_64_BASE + account_id as u64).to_string())
+            );
         }
     }
 
-    None
+    #[test]
+    fn invite_code_format() {
+        assert_eq!(invite_code(1), "c");
+        assert_eq!(invite_code(0x12345678), "cdfg-hjkm");
+        assert_eq!(
+            steam_id_from_invite_code("CDFG-HJKM"),
+            Some((STEAM_ID_64_BASE + 0x12345678).to_string())
+        );
+        assert_eq!(steam_id_from_invite_code("abc"), None);
+        assert_eq!(steam_id_from_invite_code(""), None);
+    }
+
+    #[test]
+    fn identity_converts_every_representation() {
+        let identity = identity("76561197994449443", Some("name".to_string())).unwrap();
+
+        assert_eq!(identity.account_id, 34183715);
+        assert_eq!(identity.steam2, "STEAM_1:1:17091857");
+        assert_eq!(identity.steam3, "[U:1:34183715]");
+        assert_eq!(identity.friend_code, "dbn-npdf");
+        assert_eq!(identity.friend_invite_url, "https://s.team/p/dbn-npdf");
+        assert_eq!(
+            identity.profile_url,
+            "https://steamcommunity.com/profiles/76561197994449443"
+        );
+        assert_eq!(
+            parse_steam_id(&identity.steam2).as_deref(),
+            Some("76561197994449443")
+        );
+        assert!(super::identity("12345", None).is_none());
+    }
+
+    #[rocket::async_test]
+    async fn resolve_short_link_returns_off_host_redirect() {
+        assert_eq!(
+            resolve("/p/off-host").await.ok(),
+            Some("https://steamcommunity.com/user/bcdf-ghjk".to_string())
+        );
+        assert_eq!(
+            resolve("/p/same-host").await.ok(),
+            Some("https://steamcommunity.com/user/bcdf-ghjk".to_string())
+        );
+    }
+
+    #[rocket::async_test]
+    async fn resolve_short_link_without_redirect_is_not_found() {
+        assert!(matches!(
+            resolve("/p/page").await,
+            Err(LookupError::PlayerNotFound(_))
+        ));
+    }
+
+    #[rocket::async_test]
+    async fn resolve_short_link_server_error_is_upstream_error() {
+        assert!(matches!(
+            resolve("/p/error").await,
+            Err(LookupError::SteamApi(_))
+        ));
+    }
+
+    #[rocket::async_test]
+    async fn resolve_short_link_stops_at_redirect_limit() {
+        assert!(matches!(
+            resolve("/p/loop").await,
+            Err(LookupError::PlayerNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn parse_steam_id_formats_are_equivalent() {
+        let steam_id = Some("76561197960290418".to_string());
+
+        assert_eq!(parse_steam_id("76561197960290418"), steam_id);
+        assert_eq!(parse_steam_id("STEAM_0:0:12345"), steam_id);
+        assert_eq!(parse_steam_id("STEAM_1:0:12345"), steam_id);
+        assert_eq!(parse_steam_id("[U:1:24690]"), steam_id);
+        assert_eq!(parse_steam_id("U:1:24690"), steam_id);
+        assert_eq!(parse_steam_id(" [U:1:24690] "), steam_id);
+        assert_eq!(
+            parse_steam_id("STEAM_0:1:12345"),
+            Some("76561197960290419".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_steam_id_rejects_unbalanced_steam3_brackets() {
+        assert_eq!(parse_steam_id("U:1:24690]"), None);
+        assert_eq!(parse_steam_id("[U:1:24690"), None);
+    }
+
+    #[test]
+    fn parse_steam_id_range_bounds() {
+        assert_eq!(
+            parse_steam_id("76561197960265728"),
+            Some("76561197960265728".to_string())
+        );
+        assert_eq!(parse_steam_id("76561197960265727"), None);
+        assert_eq!(
+            parse_steam_id("76561202255233023"),
+            Some("76561202255233023".to_string())
+        );
+        assert_eq!(parse_steam_id("76561202255233024"), None);
+        assert_eq!(
+            parse_steam_id("[U:1:4294967295]"),
+            Some("76561202255233023".to_string())
+        );
+        assert_eq!(parse_steam_id("[U:1:4294967296]"), None);
+        assert_eq!(parse_steam_id("STEAM_0:1:2147483648"), None);
+        assert_eq!(parse_steam_id("STEAM_0:0:9999999999999999999"), None);
+        assert_eq!(parse_steam_id("STEAM_0:1:18446744073709551615"), None);
+        assert_eq!(parse_steam_id("7656119796026572"), None);
+        assert_eq!(parse_steam_id("765611979602657280"), None);
+    }
 }
@@ -0,0 +1,37 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+
+/// Starts a local HTTP server answering every request with the raw response
+/// the handler returns for its path and query, and returns its base url
+pub fn stub_server(handler: fn(&str) -> String) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut reader = BufReader::new(&stream);
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            // Skip the headers, the stubs only route by path
+            let mut header = String::new();
+            while reader.read_line(&mut header).unwrap() > 2 {
+                header.clear();
+            }
+
+            let path = request_line.split(' ').nth(1).unwrap_or("/");
+            stream.write_all(handler(path).as_bytes()).unwrap();
+        }
+    });
+
+    url
+}
+
+pub fn response(status: &str, headers: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        headers,
+        body.len(),
+        body
+    )
+}
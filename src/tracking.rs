@@ -46,6 +46,28 @@ pub async fn track_search_request(url: &str) {
     send_event("search_request", props).await;
 }
 
+pub async fn track_steam_id_request(url: &str) {
+    let mut props = HashMap::new();
+    props.insert("url".to_string(), url.to_string());
+
+    send_event("steam_id_request", props).await;
+}
+
+/// Tracks requests to the deprecated /player mount, to know when it can be removed
+pub async fn track_legacy_request(url: &str) {
+    let mut props = HashMap::new();
+    props.insert("url".to_string(), url.to_string());
+
+    send_event("legacy_request", props).await;
+}
+
+pub async fn track_lobby_request(player_count: usize) {
+    let mut props = HashMap::new();
+    props.insert("player_count".to_string(), player_count.to_string());
+
+    send_event("lobby_request", props).await;
+}
+
 pub async fn track_error(msg: &str) {
     let mut props = HashMap::new();
     props.insert("msg".to_string(), msg.to_string());